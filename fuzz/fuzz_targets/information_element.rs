@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sbd::mo::{InformationElement, ParseOptions};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = InformationElement::read_from_with(data, &ParseOptions::lenient());
+    if let Ok(information_element) = InformationElement::read_from(data) {
+        let mut bytes = Vec::new();
+        information_element.write_to(&mut bytes).unwrap();
+        assert_eq!(information_element.len(), bytes.len());
+        assert_eq!(
+            information_element,
+            InformationElement::read_from(&bytes[..]).unwrap()
+        );
+    }
+});
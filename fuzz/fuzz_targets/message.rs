@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sbd::mo::{Message, ParseOptions};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Message::read_from_with(data, &ParseOptions::lenient());
+    if let Ok(message) = Message::read_from(data) {
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        assert_eq!(message, Message::read_from(&bytes[..]).unwrap());
+    }
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sbd::mo::{Message, MessageRef};
+
+fuzz_target!(|data: &[u8]| {
+    let explanation = Message::explain(data);
+    match MessageRef::parse(data) {
+        Ok(message) => {
+            assert!(!explanation.has_errors());
+            for information_element in message.information_elements() {
+                let _ = information_element.to_information_element();
+            }
+            assert_eq!(message.to_message(), Message::read_from(data).unwrap());
+        }
+        Err(_) => assert!(explanation.has_errors()),
+    }
+});
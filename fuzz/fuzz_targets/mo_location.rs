@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sbd::mo::MoLocation;
+
+fuzz_target!(|data: [u8; 11]| {
+    if let Ok(location) = MoLocation::parse(data) {
+        assert_eq!(data, location.to_bytes());
+        let _ = location.latitude_deg();
+        let _ = location.longitude_deg();
+        let _ = location.cep_polygon(8);
+    }
+});
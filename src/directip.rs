@@ -7,18 +7,31 @@
 //! is transmitted, then the connection is closed.
 //!
 //! This module provides a `Server` structure, which can be created to run
-//! forever and receive those incoming MO messages.
+//! forever and receive those incoming MO messages. `Server::serve` runs the server in the
+//! background and returns a `ServerHandle`, which can shut it down gracefully.
+//!
+//! The server can also acknowledge each message with an MO confirmation, so the gateway knows
+//! whether the message was stored.
 
 use std::{
-    io,
-    net::{TcpListener, TcpStream, ToSocketAddrs},
-    sync::{Arc, Mutex},
-    thread,
+    io::{self, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use byteorder::{BigEndian, ByteOrder};
 use log::{debug, error, info, warn};
 
-use crate::{mo::Message, storage::Storage};
+use crate::{
+    mo::{InformationElement, Message, PROTOCOL_REVISION_NUMBER},
+    storage::Storage,
+    Error,
+};
 
 /// A Iridium `DirectIP` server.
 ///
@@ -30,6 +43,9 @@ pub struct Server<A: ToSocketAddrs + Sync, S: Storage + Sync + Send> {
     addr: A,
     listener: Option<TcpListener>,
     storage: Arc<Mutex<S>>,
+    confirm: bool,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
 }
 
 impl<A, S> Server<A, S>
@@ -55,12 +71,48 @@ where
             addr,
             listener: None,
             storage: Arc::new(Mutex::new(storage)),
+            confirm: false,
+            read_timeout: Some(Duration::from_secs(30)),
+            write_timeout: Some(Duration::from_secs(30)),
         }
     }
 
+    /// Sets whether to send an MO confirmation back to the gateway for each message.
+    ///
+    /// The confirmation is written after the message has been stored. It reports success if the
+    /// message was stored, and failure if the message couldn't be read or stored. Confirmations
+    /// are off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let storage = sbd::storage::MemoryStorage::new();
+    /// let server = sbd::directip::Server::new("0.0.0.0:10800", storage).confirm(true);
+    /// ```
+    pub fn confirm(mut self, confirm: bool) -> Server<A, S> {
+        self.confirm = confirm;
+        self
+    }
+
+    /// Sets the timeout for reading a message from each connection, or `None` to wait forever.
+    ///
+    /// Defaults to thirty seconds, so a connection that stops sending doesn't hold up a shutdown.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Server<A, S> {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout for writing a confirmation to each connection, or `None` to wait forever.
+    ///
+    /// Defaults to thirty seconds.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Server<A, S> {
+        self.write_timeout = timeout;
+        self
+    }
+
     /// Binds this server to its tcp socket.
     ///
-    /// This is a seperate operation from `serve_forever` so that we can capture any errors
+    /// This is a seperate operation from `serve` and `serve_forever` so that we can capture any errors
     /// associated with the underlying `TcpListener::bind`.
     ///
     /// # Examples
@@ -75,6 +127,77 @@ where
         Ok(())
     }
 
+    /// Starts the DirectIP server in the background.
+    ///
+    /// The server is bound first, if `Server::bind` hasn't been called already. Each connection
+    /// is handled on its own thread. Use the returned handle to shut the server down, or to wait
+    /// for it to stop. Dropping the handle leaves the server running.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// let storage = sbd::storage::MemoryStorage::new();
+    /// let server = sbd::directip::Server::new("127.0.0.1:0", storage);
+    /// let handle = server.serve().unwrap();
+    /// println!("listening on {}", handle.local_addr());
+    /// assert!(handle.shutdown(Duration::from_secs(10)));
+    /// ```
+    pub fn serve(mut self) -> io::Result<ServerHandle> {
+        let listener = match self.listener.take() {
+            Some(listener) => listener,
+            None => self.create_listener()?,
+        };
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                accepting: true,
+                in_flight: 0,
+            }),
+            changed: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+        let handle = {
+            let shared = Arc::clone(&shared);
+            let storage = self.storage;
+            let confirm = self.confirm;
+            let (read_timeout, write_timeout) = (self.read_timeout, self.write_timeout);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shared.shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    match stream {
+                        Ok(stream) => {
+                            if let Err(err) = stream
+                                .set_read_timeout(read_timeout)
+                                .and_then(|()| stream.set_write_timeout(write_timeout))
+                            {
+                                warn!("Could not set timeouts on TcpStream: {:?}", err);
+                            }
+                            let storage = Arc::clone(&storage);
+                            let in_flight = InFlight::new(Arc::clone(&shared));
+                            thread::spawn(move || {
+                                handle_stream(stream, storage, confirm);
+                                drop(in_flight);
+                            });
+                        }
+                        Err(err) => handle_error(&err),
+                    }
+                }
+                info!("Stopped accepting connections");
+                shared.state().accepting = false;
+                shared.changed.notify_all();
+            })
+        };
+        info!("Listening on {}", addr);
+        Ok(ServerHandle {
+            addr,
+            shared,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
     /// Starts the DirectIP server and serves forever.
     ///
     /// # Panics
@@ -90,34 +213,167 @@ where
     /// server.bind().unwrap();
     /// server.serve_forever();
     /// ```
-    pub fn serve_forever(mut self) {
-        let listener = match self.listener {
-            Some(ref listener) => listener,
-            None => {
-                self.listener = Some(self.create_listener().unwrap());
-                self.listener.as_ref().unwrap()
+    pub fn serve_forever(self) {
+        self.serve().unwrap().join();
+    }
+
+    fn create_listener(&self) -> io::Result<TcpListener> {
+        TcpListener::bind(&self.addr)
+    }
+}
+
+/// A handle to a running `Server`.
+///
+/// The handle can be shared between threads, e.g. so one thread can wait for the server with
+/// `join` while another shuts it down when the process is asked to stop.
+#[derive(Debug)]
+pub struct ServerHandle {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// The state shared between a `ServerHandle` and its server's threads.
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    shutdown: AtomicBool,
+}
+
+#[derive(Debug)]
+struct State {
+    accepting: bool,
+    in_flight: usize,
+}
+
+/// Counts a connection as in flight until it's dropped, even if handling the connection panics.
+#[derive(Debug)]
+struct InFlight(Arc<Shared>);
+
+impl ServerHandle {
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Shuts the server down.
+    ///
+    /// The server stops accepting new connections, then waits for the connections it has
+    /// already accepted to be read and stored. Returns true once they're all done, or false if
+    /// some are still going when `timeout` runs out. Those connections keep going in the
+    /// background.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        if !self.shared.shutdown.swap(true, Ordering::SeqCst) {
+            info!("Shutting down");
+            // Wake up the accept loop so it sees the shutdown flag, without waiting past the
+            // deadline. A zero timeout is an error, so always allow a moment to connect.
+            let timeout = deadline
+                .saturating_duration_since(Instant::now())
+                .max(Duration::from_millis(1));
+            let _ = TcpStream::connect_timeout(&wake_addr(self.addr), timeout);
+        }
+        let state = self.shared.wait_until(deadline, |state| !state.accepting);
+        if !state.accepting {
+            drop(state);
+            if let Some(handle) = self.handle.lock().expect("unable to lock handle").take() {
+                let _ = handle.join();
             }
-        };
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    let storage = Arc::clone(&self.storage);
-                    thread::spawn(move || handle_stream(stream, storage));
-                }
-                Err(err) => {
-                    thread::spawn(move || handle_error(&err));
+        }
+        let state = self
+            .shared
+            .wait_until(deadline, |state| state.in_flight == 0);
+        if state.in_flight > 0 {
+            warn!(
+                "Shut down with {} connection(s) still in flight",
+                state.in_flight
+            );
+        }
+        !state.accepting && state.in_flight == 0
+    }
+
+    /// Waits for the server to stop.
+    ///
+    /// Returns once the server has been shut down and every connection it accepted is done.
+    pub fn join(&self) {
+        drop(
+            self.shared
+                .wait_until(None, |state| !state.accepting && state.in_flight == 0),
+        );
+    }
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("unable to lock server state")
+    }
+
+    /// Waits until a condition holds or the deadline passes, and returns the state.
+    fn wait_until<D: Into<Option<Instant>>, F: Fn(&State) -> bool>(
+        &self,
+        deadline: D,
+        condition: F,
+    ) -> MutexGuard<'_, State> {
+        let deadline = deadline.into();
+        let mut state = self.state();
+        while !condition(&state) {
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    self.changed
+                        .wait_timeout(state, deadline - now)
+                        .expect("unable to lock server state")
+                        .0
                 }
-            }
+                None => self
+                    .changed
+                    .wait(state)
+                    .expect("unable to lock server state"),
+            };
         }
+        state
     }
+}
 
-    fn create_listener(&self) -> io::Result<TcpListener> {
-        TcpListener::bind(&self.addr)
+impl InFlight {
+    fn new(shared: Arc<Shared>) -> InFlight {
+        shared.state().in_flight += 1;
+        InFlight(shared)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.state().in_flight -= 1;
+        self.0.changed.notify_all();
+    }
+}
+
+/// Returns an address that reaches a listener bound to `addr`.
+///
+/// A listener bound to the unspecified address can't be connected to on every platform, so use
+/// loopback instead.
+fn wake_addr(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V4(v4) if v4.ip().is_unspecified() => {
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), v4.port())
+        }
+        SocketAddr::V6(v6) if v6.ip().is_unspecified() => {
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), v6.port())
+        }
+        addr => addr,
     }
 }
 
 /// Handles an incoming `DirectIP` stream.
-fn handle_stream(stream: TcpStream, storage: Arc<Mutex<dyn Storage>>) {
+///
+/// If `confirm` is true, writes an MO confirmation back to the stream once the message has been
+/// stored, or once it's clear that it won't be.
+fn handle_stream(mut stream: TcpStream, storage: Arc<Mutex<dyn Storage>>, confirm: bool) {
     match stream.peer_addr() {
         Ok(addr) => {
             debug!("Handling TcpStream from {}", addr);
@@ -129,7 +385,7 @@ fn handle_stream(stream: TcpStream, storage: Arc<Mutex<dyn Storage>>) {
             );
         }
     }
-    let message = match Message::read_from(stream) {
+    let message = match Message::read_from(&mut stream) {
         Ok(message) => {
             info!(
                 "Received message from IMEI {} with MOMN {} and {} byte payload",
@@ -141,19 +397,50 @@ fn handle_stream(stream: TcpStream, storage: Arc<Mutex<dyn Storage>>) {
         }
         Err(err) => {
             error!("Error when reading message: {:?}", err);
+            if confirm {
+                send_confirmation(&mut stream, false);
+            }
             return;
         }
     };
-    match storage
+    let stored = match storage
         .lock()
         .expect("unable to lock storage mutex")
         .store(message)
     {
-        Ok(_) => info!("Stored message"),
-        Err(err) => error!("Problem storing message: {:?}", err),
+        Ok(_) => {
+            info!("Stored message");
+            true
+        }
+        Err(err) => {
+            error!("Problem storing message: {:?}", err);
+            false
+        }
+    };
+    if confirm {
+        send_confirmation(&mut stream, stored);
+    }
+}
+
+/// Sends an MO confirmation, logging any problems.
+fn send_confirmation(stream: &mut TcpStream, success: bool) {
+    match write_confirmation(stream, success) {
+        Ok(()) => debug!("Sent confirmation with status {}", u8::from(success)),
+        Err(err) => warn!("Could not send confirmation: {:?}", err),
     }
 }
 
+/// Writes an MO confirmation message, which is a single confirmation information element.
+fn write_confirmation<W: Write>(mut write: W, success: bool) -> Result<(), Error> {
+    let information_element = InformationElement::Confirmation(success);
+    let mut bytes = vec![0; 3 + information_element.len()];
+    bytes[0] = PROTOCOL_REVISION_NUMBER;
+    BigEndian::write_u16(&mut bytes[1..3], information_element.len() as u16);
+    let _ = information_element.encode_into(&mut bytes[3..])?;
+    write.write_all(&bytes)?;
+    Ok(())
+}
+
 /// Handles an error when handling a connection.
 fn handle_error(err: &io::Error) {
     error!("Error when receiving tcp communication: {:?}", err);
@@ -161,8 +448,91 @@ fn handle_error(err: &io::Error) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::storage::MemoryStorage;
-    use std::{fs, io::Cursor, path::Path};
+    use crate::{
+        storage::{FilesystemStorage, MemoryStorage},
+        Imei,
+    };
+    use std::{fs, io::Cursor, io::Read, path::Path};
+    use tempdir::TempDir;
+
+    /// A storage that can't store anything.
+    struct FullStorage;
+
+    impl Storage for FullStorage {
+        fn store(&mut self, _: Message) -> Result<(), Error> {
+            Err(io::Error::other("storage is full").into())
+        }
+
+        fn messages(&self) -> Result<Vec<Message>, Error> {
+            Ok(Vec::new())
+        }
+
+        fn messages_from_imei(&self, _: Imei) -> Result<Vec<Message>, Error> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Sends bytes to `handle_stream` and returns whatever it writes back.
+    fn exchange(bytes: Vec<u8>, storage: Arc<Mutex<dyn Storage + Send>>, confirm: bool) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(&bytes).unwrap();
+            stream.shutdown(std::net::Shutdown::Write).unwrap();
+            let mut reply = Vec::new();
+            stream.read_to_end(&mut reply).unwrap();
+            reply
+        });
+        let (stream, _) = listener.accept().unwrap();
+        handle_stream(stream, storage, confirm);
+        handle.join().unwrap()
+    }
+
+    #[test]
+    fn confirmation_bytes() {
+        let mut bytes = Vec::new();
+        write_confirmation(&mut bytes, true).unwrap();
+        assert_eq!(vec![0x01, 0x00, 0x04, 0x05, 0x00, 0x01, 0x01], bytes);
+        bytes.clear();
+        write_confirmation(&mut bytes, false).unwrap();
+        assert_eq!(vec![0x01, 0x00, 0x04, 0x05, 0x00, 0x01, 0x00], bytes);
+    }
+
+    #[test]
+    fn confirm_stored_message() {
+        let storage = Arc::new(Mutex::new(MemoryStorage::new()));
+        let reply = exchange(fs::read("data/0-mo.sbd").unwrap(), storage.clone(), true);
+        assert_eq!(vec![0x01, 0x00, 0x04, 0x05, 0x00, 0x01, 0x01], reply);
+        assert_eq!(1, storage.lock().unwrap().messages().unwrap().len());
+    }
+
+    #[test]
+    fn confirm_storage_failure() {
+        let reply = exchange(
+            fs::read("data/0-mo.sbd").unwrap(),
+            Arc::new(Mutex::new(FullStorage)),
+            true,
+        );
+        assert_eq!(vec![0x01, 0x00, 0x04, 0x05, 0x00, 0x01, 0x00], reply);
+    }
+
+    #[test]
+    fn confirm_unreadable_message() {
+        let storage = Arc::new(Mutex::new(MemoryStorage::new()));
+        let mut bytes = fs::read("data/0-mo.sbd").unwrap();
+        bytes.truncate(10);
+        let reply = exchange(bytes, storage, true);
+        assert_eq!(vec![0x01, 0x00, 0x04, 0x05, 0x00, 0x01, 0x00], reply);
+    }
+
+    #[test]
+    fn no_confirmation() {
+        let storage = Arc::new(Mutex::new(MemoryStorage::new()));
+        let reply = exchange(fs::read("data/0-mo.sbd").unwrap(), storage.clone(), false);
+        assert!(reply.is_empty());
+        assert_eq!(1, storage.lock().unwrap().messages().unwrap().len());
+    }
 
     #[test]
     fn test_store_real_message_from_stream_file() {
@@ -198,4 +568,112 @@ mod tests {
 
         assert!(result.is_ok(), "Real message should be stored successfully");
     }
+
+    /// Starts a server that stores messages in a temporary directory.
+    fn serve(tempdir: &TempDir) -> ServerHandle {
+        let storage = FilesystemStorage::open(tempdir.path()).unwrap();
+        let mut server = Server::new("127.0.0.1:0", storage).confirm(true);
+        server.bind().unwrap();
+        server.serve().unwrap()
+    }
+
+    fn stored(tempdir: &TempDir) -> usize {
+        FilesystemStorage::open(tempdir.path())
+            .unwrap()
+            .messages()
+            .unwrap()
+            .len()
+    }
+
+    #[test]
+    fn serve_and_shutdown() {
+        let tempdir = TempDir::new("").unwrap();
+        let handle = serve(&tempdir);
+        let mut stream = TcpStream::connect(handle.local_addr()).unwrap();
+        stream
+            .write_all(&fs::read("data/0-mo.sbd").unwrap())
+            .unwrap();
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).unwrap();
+        assert_eq!(vec![0x01, 0x00, 0x04, 0x05, 0x00, 0x01, 0x01], reply);
+
+        assert!(handle.shutdown(Duration::from_secs(5)));
+        assert!(TcpStream::connect(handle.local_addr()).is_err());
+        assert_eq!(1, stored(&tempdir));
+        assert!(handle.shutdown(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn shutdown_waits_for_in_flight_messages() {
+        let tempdir = TempDir::new("").unwrap();
+        let handle = serve(&tempdir);
+        let bytes = fs::read("data/0-mo.sbd").unwrap();
+        let mut stream = TcpStream::connect(handle.local_addr()).unwrap();
+        stream.write_all(&bytes[..10]).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let client = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            stream.write_all(&bytes[10..]).unwrap();
+            let mut reply = Vec::new();
+            stream.read_to_end(&mut reply).unwrap();
+            reply
+        });
+        assert!(handle.shutdown(Duration::from_secs(5)));
+        assert_eq!(1, stored(&tempdir));
+        assert_eq!(
+            vec![0x01, 0x00, 0x04, 0x05, 0x00, 0x01, 0x01],
+            client.join().unwrap()
+        );
+    }
+
+    #[test]
+    fn shutdown_deadline() {
+        let tempdir = TempDir::new("").unwrap();
+        let handle = serve(&tempdir);
+        let mut stream = TcpStream::connect(handle.local_addr()).unwrap();
+        stream
+            .write_all(&fs::read("data/0-mo.sbd").unwrap()[..10])
+            .unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        assert!(!handle.shutdown(Duration::from_millis(50)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(TcpStream::connect(handle.local_addr()).is_err());
+    }
+
+    #[test]
+    fn partial_message_times_out() {
+        let tempdir = TempDir::new("").unwrap();
+        let storage = FilesystemStorage::open(tempdir.path()).unwrap();
+        let handle = Server::new("127.0.0.1:0", storage)
+            .read_timeout(Some(Duration::from_millis(100)))
+            .serve()
+            .unwrap();
+        let mut stream = TcpStream::connect(handle.local_addr()).unwrap();
+        stream
+            .write_all(&fs::read("data/0-mo.sbd").unwrap()[..10])
+            .unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        let _ = handle.shutdown(Duration::from_millis(10));
+        handle.join();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(0, stored(&tempdir));
+        drop(stream);
+    }
+
+    #[test]
+    fn join_after_shutdown() {
+        let tempdir = TempDir::new("").unwrap();
+        let handle = Arc::new(serve(&tempdir));
+        let shutdown = {
+            let handle = Arc::clone(&handle);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                handle.shutdown(Duration::from_secs(5))
+            })
+        };
+        handle.join();
+        assert!(shutdown.join().unwrap());
+    }
 }
@@ -1,28 +1,133 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+#[cfg(feature = "std")]
 use std::path::PathBuf;
 
 use thiserror::Error;
 
-use crate::mo::Header;
+use crate::{
+    mo::{Header, MoLocationError},
+    Imei,
+};
 
 /// Crate-specific error enum.
 #[derive(Debug, Error)]
 pub enum Error {
+    /// The buffer is too small to encode the message into.
+    #[error("buffer too small: needed {needed} bytes, but only {available} available")]
+    BufferTooSmall {
+        /// The number of bytes needed.
+        needed: usize,
+        /// The size of the buffer.
+        available: usize,
+    },
+
+    /// The gateway confirmed a different MT message than the one that was sent.
+    #[error(
+        "confirmation is for message {confirmed_id} to IMEI {confirmed_imei}, but message {sent_id} \
+         to IMEI {sent_imei} was sent"
+    )]
+    ConfirmationMismatch {
+        /// The unique client message id of the message that was sent.
+        sent_id: u32,
+        /// The IMEI the message was sent to.
+        sent_imei: Imei,
+        /// The unique client message id in the confirmation.
+        confirmed_id: u32,
+        /// The IMEI in the confirmation.
+        confirmed_imei: Imei,
+    },
+
+    /// An information element appears more than once in a message.
+    #[error("information element {0} appears more than once")]
+    DuplicateInformationElement(u8),
+
+    /// The payload is empty, but the message requires at least one byte.
+    #[error("empty payload")]
+    EmptyPayload,
+
+    /// The confirmation status is neither success (1) nor failure (0).
+    #[error("invalid confirmation status: {0}")]
+    InvalidConfirmationStatus(u8),
+
+    /// An information element is too long to be written.
+    #[error("the information element is too long at {0} bytes")]
+    InformationElementTooLong(usize),
+
+    /// An information element could not be read.
+    ///
+    /// The offset is counted in bytes from the start of the message, i.e. the protocol revision
+    /// number is at offset zero.
+    #[error("information element {iei} at byte offset {offset}: {source}")]
+    InformationElement {
+        /// The information element identifier.
+        iei: u8,
+        /// The offset of the start of the information element.
+        offset: usize,
+        /// The underlying error.
+        source: Box<Error>,
+    },
+
+    /// A fixed-size information element declared the wrong length.
+    #[error("information element {iei} should have length {expected}, but declared {actual}")]
+    InformationElementLength {
+        /// The information element identifier.
+        iei: u8,
+        /// The length required by the specification.
+        expected: u16,
+        /// The length declared in the message.
+        actual: u16,
+    },
+
     /// The identifier is invalid.
     #[error("invalid information element identifier: {0}")]
     InvalidInformationElementIdentifier(u8),
 
+    /// The IMEI is not fifteen ASCII digits.
+    #[error("invalid IMEI: {0:?}")]
+    InvalidImei(String),
+
+    /// The last digit of the IMEI is not the Luhn check digit.
+    #[error("invalid IMEI check digit: {imei} should end in {expected}")]
+    InvalidImeiCheckDigit {
+        /// The IMEI.
+        imei: Imei,
+        /// The expected check digit.
+        expected: u8,
+    },
+
+    /// The MT priority is not between 1 and 5.
+    #[error("invalid priority: {0}")]
+    InvalidPriority(u16),
+
     /// The message has an invalid protocol revision number.
     #[error("invalid protocol revision number: {0}")]
     InvalidProtocolRevisionNumber(u8),
 
+    /// A queued MT confirmation status has a queue position outside of 1 to 50.
+    #[error("invalid queue position: {0}")]
+    InvalidQueuePosition(u8),
+
     /// Invalid time of session.
     #[error("invalid time of session")]
     InvalidTimeOfSession,
 
+    /// The location information could not be parsed.
+    #[error("invalid location: {0}")]
+    InvalidLocation(#[from] MoLocationError),
+
     /// IO error.
+    #[cfg(feature = "std")]
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// A required field was not provided.
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
+
+    /// A required information element is not in the message.
+    #[error("missing information element: {0}")]
+    MissingInformationElement(u8),
+
     /// The overall message length is too big.
     #[error("the overall message length is too big: {0}")]
     OverallMessageLength(usize),
@@ -36,6 +141,7 @@ pub enum Error {
     NoHeader,
 
     /// The path is not a directory.
+    #[cfg(feature = "std")]
     #[error("not a directory: {}", .0.display())]
     NotADirectory(PathBuf),
 
@@ -55,11 +161,27 @@ pub enum Error {
     #[error("two payloads")]
     TwoPayloads(Vec<u8>, Vec<u8>),
 
+    /// There are bytes after the end of the message.
+    ///
+    /// When reading from a stream, at most about one message's worth of trailing bytes is counted.
+    #[error("{0} trailing bytes after the end of the message")]
+    TrailingBytes(usize),
+
+    /// The data ended before the message or information element was complete.
+    #[error("unexpected end of data: needed {needed} bytes, but only {available} available")]
+    UnexpectedEnd {
+        /// The number of bytes needed.
+        needed: usize,
+        /// The number of bytes available.
+        available: usize,
+    },
+
     /// Unknown session status code.
     #[error("unknown session status: {0}")]
     UnknownSessionStatus(u8),
 
     /// Walkdir error.
+    #[cfg(feature = "std")]
     #[error("walkdir error: {0}")]
     Walkdir(#[from] walkdir::Error),
 }
@@ -0,0 +1,289 @@
+//! International Mobile Equipment Identity (IMEI) numbers.
+
+use alloc::string::{String, ToString};
+use core::{fmt, str::FromStr};
+
+use crate::Error;
+
+/// An International Mobile Equipment Identity, the number that identifies an Iridium device.
+///
+/// An IMEI is fifteen ASCII digits: an eight digit type allocation code (TAC), a six digit serial
+/// number, and a check digit.
+///
+/// Creating an `Imei` only checks the format, not the check digit. The IMEIs reported by the
+/// Iridium gateway, including the examples in the Developers Guide, don't always end in a valid
+/// Luhn check digit, so rejecting them would lose real messages. Use
+/// `Imei::validate_check_digit` if you need the stricter check.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::Imei;
+/// let imei: Imei = "300234063904190".parse().unwrap();
+/// assert_eq!("30023406", imei.tac());
+/// assert_eq!("390419", imei.serial_number());
+/// assert!("30023406390419".parse::<Imei>().is_err());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Imei([u8; 15]);
+
+impl Imei {
+    /// Creates an IMEI from its fifteen ASCII bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImei` if any of the bytes isn't an ASCII digit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei = Imei::new(*b"300234063904190").unwrap();
+    /// assert!(Imei::new([0; 15]).is_err());
+    /// ```
+    pub fn new(bytes: [u8; 15]) -> Result<Imei, Error> {
+        if bytes.iter().all(u8::is_ascii_digit) {
+            Ok(Imei(bytes))
+        } else {
+            Err(Error::InvalidImei(
+                String::from_utf8_lossy(&bytes).into_owned(),
+            ))
+        }
+    }
+
+    /// Returns this IMEI as a string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei: Imei = "300234063904190".parse().unwrap();
+    /// assert_eq!("300234063904190", imei.as_str());
+    /// ```
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.0).expect("IMEIs are validated to be ASCII digits")
+    }
+
+    /// Returns this IMEI as ASCII bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei: Imei = "300234063904190".parse().unwrap();
+    /// assert_eq!(b"300234063904190", imei.as_bytes());
+    /// ```
+    pub fn as_bytes(&self) -> &[u8; 15] {
+        &self.0
+    }
+
+    /// Returns the type allocation code, the first eight digits.
+    ///
+    /// The TAC identifies the manufacturer and model of the device.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei: Imei = "300234063904190".parse().unwrap();
+    /// assert_eq!("30023406", imei.tac());
+    /// ```
+    pub fn tac(&self) -> &str {
+        &self.as_str()[..8]
+    }
+
+    /// Returns the serial number, the six digits after the type allocation code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei: Imei = "300234063904190".parse().unwrap();
+    /// assert_eq!("390419", imei.serial_number());
+    /// ```
+    pub fn serial_number(&self) -> &str {
+        &self.as_str()[8..14]
+    }
+
+    /// Returns the last digit of the IMEI, which should be the check digit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei: Imei = "490154203237518".parse().unwrap();
+    /// assert_eq!(8, imei.check_digit());
+    /// ```
+    pub fn check_digit(&self) -> u8 {
+        self.0[14] - b'0'
+    }
+
+    /// Computes the Luhn check digit from the first fourteen digits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei: Imei = "300234063904190".parse().unwrap();
+    /// assert_eq!(9, imei.luhn_check_digit());
+    /// ```
+    pub fn luhn_check_digit(&self) -> u8 {
+        let sum: u8 = self.0[..14]
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                let digit = b - b'0';
+                if i % 2 == 1 {
+                    let doubled = digit * 2;
+                    if doubled > 9 {
+                        doubled - 9
+                    } else {
+                        doubled
+                    }
+                } else {
+                    digit
+                }
+            })
+            .sum();
+        (10 - sum % 10) % 10
+    }
+
+    /// Checks that the last digit is the Luhn check digit.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImeiCheckDigit` if the check digit is wrong.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::Imei;
+    /// let imei: Imei = "490154203237518".parse().unwrap();
+    /// assert!(imei.validate_check_digit().is_ok());
+    /// let imei: Imei = "490154203237517".parse().unwrap();
+    /// assert!(imei.validate_check_digit().is_err());
+    /// ```
+    pub fn validate_check_digit(&self) -> Result<(), Error> {
+        let expected = self.luhn_check_digit();
+        if self.check_digit() == expected {
+            Ok(())
+        } else {
+            Err(Error::InvalidImeiCheckDigit {
+                imei: *self,
+                expected,
+            })
+        }
+    }
+}
+
+impl FromStr for Imei {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Imei, Error> {
+        s.as_bytes()
+            .try_into()
+            .map_err(|_| Error::InvalidImei(s.to_string()))
+            .and_then(Imei::new)
+    }
+}
+
+impl TryFrom<[u8; 15]> for Imei {
+    type Error = Error;
+
+    fn try_from(bytes: [u8; 15]) -> Result<Imei, Error> {
+        Imei::new(bytes)
+    }
+}
+
+impl AsRef<str> for Imei {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for Imei {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Imei {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<Imei> for &str {
+    fn eq(&self, other: &Imei) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl fmt::Display for Imei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Imei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Imei").field(&self.as_str()).finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Imei {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Imei {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Imei, D::Error> {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let imei: Imei = "300234063904190".parse().unwrap();
+        assert_eq!("300234063904190", imei.to_string());
+        assert!("".parse::<Imei>().is_err());
+        assert!("3002340639041900".parse::<Imei>().is_err());
+        assert!("30023406390419x".parse::<Imei>().is_err());
+        assert!("３００２３４０６３９０４".parse::<Imei>().is_err());
+    }
+
+    #[test]
+    fn check_digit() {
+        for s in ["490154203237518", "356938035643809", "352099001761481"] {
+            let imei: Imei = s.parse().unwrap();
+            assert!(imei.validate_check_digit().is_ok(), "{}", s);
+        }
+        let imei: Imei = "300234063904190".parse().unwrap();
+        assert!(imei.validate_check_digit().is_err());
+    }
+
+    #[test]
+    fn order() {
+        let a: Imei = "300234063904190".parse().unwrap();
+        let b: Imei = "301434061799480".parse().unwrap();
+        assert!(a < b);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let imei: Imei = "300234063904190".parse().unwrap();
+        let json = serde_json::to_string(&imei).unwrap();
+        assert_eq!("\"300234063904190\"", json);
+        assert_eq!(imei, serde_json::from_str::<Imei>(&json).unwrap());
+        assert!(serde_json::from_str::<Imei>("\"3002340639041\"").is_err());
+    }
+}
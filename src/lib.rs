@@ -17,8 +17,8 @@
 //! # Usage
 //!
 //! This is a simple library for reading mobile originated SBD messages from a stream, decoding
-//! their headers and data payloads, and writing them back to a stream. This library does not
-//! handle mobile terminated messages.
+//! their headers and data payloads, and writing them back to a stream. Mobile terminated
+//! messages, for sending data to a device, are in the `mt` module.
 //!
 //! MO messages can be read from a byte stream:
 //!
@@ -27,6 +27,9 @@
 //! let message = sbd::mo::Message::read_from(file).unwrap();
 //! ```
 //!
+//! To send MT messages to an Iridium gateway, use `mt::Client`, or `mt::Outbox` to queue them,
+//! retry them, and track their delivery.
+//!
 //! To receive MO messages via `DirectIP`, a server is provided.
 //! This server will listen for incoming messages forever, storing them in a `Storage`:
 //!
@@ -36,9 +39,22 @@
 //! server.serve_forever();
 //! ```
 //!
+//! `Server::serve` runs the server in the background instead, and returns a handle that can shut
+//! it down without losing messages that are still arriving.
+//!
 //! Most of the functionality of this library is exposed by a single executable, named `sbd`.  Use
 //! the `sbd` executable to inspect raw sbd files stores on a filesystem, interrogate sbd files on a
 //! filesystem, and start that forever-running server to receive Iridium SBD `DirectIP` messages.
+//!
+//! # Features
+//!
+//! - `std` (default): reading and writing with `std::io`, `Message::from_path`, the `storage`,
+//!   `directip`, `testing`, and `timeline` modules, `mt::Client` and `mt::Outbox`, and the geometry
+//!   helpers on `mo::MoLocation`. Without it, this crate is `no_std` and only needs `alloc`; use
+//!   `mo::Message::decode` and `mo::Message::encode_into` to work with byte slices.
+//! - `serde` (default): `Serialize` and `Deserialize` for the message types.
+//! - `tokio`: `mo::Message::read_from_async` and `mo::Message::write_to_async`, and `mo::MoCodec`
+//!   for framing messages with `tokio_util::codec`.
 
 #![deny(
     missing_copy_implementations,
@@ -53,10 +69,21 @@
     unused_qualifications
 )]
 #![recursion_limit = "128"]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
 pub mod directip;
 mod error;
+mod imei;
 pub mod mo;
+pub mod mt;
+#[cfg(feature = "std")]
 pub mod storage;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod timeline;
 
-pub use error::Error;
+pub use crate::{error::Error, imei::Imei};
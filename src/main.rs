@@ -5,18 +5,19 @@ use std::{io::Write, path::Path, process, str};
 use docopt::Docopt;
 use sbd::{
     directip::Server,
-    mo::{Message, SessionStatus},
+    mo::{Message, PayloadEncoding},
     storage::FilesystemStorage,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
 const USAGE: &str = "
 Iridium Short Burst Data (SBD) message utility.
 
 Usage:
-    sbd info <file> [--compact]
+    sbd info <file> [--compact] [--hex]
+    sbd explain <file> [--json]
     sbd payload <file>
-    sbd serve <addr> <directory> [--logfile=<logfile>]
+    sbd serve <addr> <directory> [--logfile=<logfile>] [--confirm]
     sbd (-h | --help)
     sbd --version
 
@@ -24,37 +25,32 @@ Options:
     -h --help               Show this information
     --version               Show version
     --logfile=<logfile>     Logfile [default: /var/log/iridiumd.log]
+    --confirm               Send an MO confirmation after storing each message
     --compact               Don't pretty-print the JSON
+    --hex                   Encode the payload as hex instead of base64
+    --json                  Print the explanation as JSON
 ";
 
 #[derive(Debug, Deserialize)]
 struct Args {
     cmd_info: bool,
+    cmd_explain: bool,
     cmd_payload: bool,
     cmd_serve: bool,
     arg_addr: String,
     arg_directory: String,
     arg_file: String,
     flag_logfile: String,
+    flag_confirm: bool,
     flag_compact: bool,
+    flag_hex: bool,
+    flag_json: bool,
 }
 
 struct Logger<P: AsRef<Path>> {
     path: P,
 }
 
-#[derive(Debug, Serialize)]
-struct ReadableMessage {
-    protocol_revision_number: u8,
-    cdr_reference: u32,
-    imei: String,
-    session_status: SessionStatus,
-    momsn: u16,
-    mtmsn: u16,
-    time_of_session: String,
-    payload: String,
-}
-
 impl<P: AsRef<Path> + Send + Sync> log::Log for Logger<P> {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
         metadata.level() <= log::Level::Debug
@@ -91,12 +87,6 @@ impl<P: AsRef<Path> + Send + Sync> log::Log for Logger<P> {
     fn flush(&self) {}
 }
 
-impl ReadableMessage {
-    fn new(_: &Message) -> ReadableMessage {
-        unimplemented!()
-    }
-}
-
 fn main() {
     let args: Args = Docopt::new(USAGE)
         .map(|d| d.version(Some(env!("CARGO_PKG_VERSION").to_string())))
@@ -106,7 +96,12 @@ fn main() {
     if args.cmd_info {
         match Message::from_path(&args.arg_file) {
             Ok(ref message) => {
-                let message = &ReadableMessage::new(message);
+                let encoding = if args.flag_hex {
+                    PayloadEncoding::Hex
+                } else {
+                    PayloadEncoding::Base64
+                };
+                let message = &message.with_payload_encoding(encoding);
                 if args.flag_compact {
                     println!("{}", serde_json::to_string(message).unwrap());
                 } else {
@@ -119,6 +114,25 @@ fn main() {
             }
         }
     }
+    if args.cmd_explain {
+        match std::fs::read(&args.arg_file) {
+            Ok(bytes) => {
+                let annotation = Message::explain(&bytes);
+                if args.flag_json {
+                    println!("{}", serde_json::to_string_pretty(&annotation).unwrap());
+                } else {
+                    print!("{}", annotation);
+                }
+                if annotation.has_errors() {
+                    process::exit(1);
+                }
+            }
+            Err(err) => {
+                println!("ERROR: Unable to read file: {}", err);
+                process::exit(1);
+            }
+        }
+    }
     if args.cmd_payload {
         match Message::from_path(&args.arg_file) {
             Ok(ref message) => match str::from_utf8(message.payload()) {
@@ -148,7 +162,7 @@ fn main() {
             println!("ERROR: Could not open storage: {}", e);
             process::exit(1);
         });
-        let mut server = Server::new(&args.arg_addr[..], storage);
+        let mut server = Server::new(&args.arg_addr[..], storage).confirm(args.flag_confirm);
         match server.bind() {
             Ok(()) => server.serve_forever(),
             Err(err) => {
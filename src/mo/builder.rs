@@ -0,0 +1,291 @@
+//! Build mobile-originated messages in code.
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    mo::{Header, InformationElement, Message, MoLocation, SessionStatus},
+    Error, Imei,
+};
+
+/// The maximum length of a mobile-originated payload, in bytes.
+///
+/// This is the largest MO message supported by any Iridium transceiver, per the Developers
+/// Guide. Smaller transceivers (e.g. the 9601 and 9602) only support 340 bytes.
+pub const MAX_PAYLOAD_LENGTH: usize = 1960;
+
+/// A builder for mobile-originated messages.
+///
+/// Unlike `Message::new`, the builder checks its inputs against the limits in the
+/// specification when `build` is called.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use sbd::mo::MessageBuilder;
+/// let message = MessageBuilder::new()
+///     .imei("300234063904190")
+///     .momsn(1)
+///     .time_of_session(Utc.with_ymd_and_hms(2017, 10, 1, 0, 0, 0).unwrap())
+///     .payload(b"hello world".to_vec())
+///     .build()
+///     .unwrap();
+/// assert_eq!("300234063904190", message.imei());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MessageBuilder {
+    auto_id: u32,
+    imei: Option<String>,
+    session_status: Option<SessionStatus>,
+    momsn: u16,
+    mtmsn: u16,
+    time_of_session: Option<DateTime<Utc>>,
+    payload: Vec<u8>,
+    location_information: Option<[u8; 11]>,
+}
+
+impl MessageBuilder {
+    /// Creates a new builder.
+    ///
+    /// The IMEI and the time of session are required. Everything else defaults to zero or empty,
+    /// and the session status defaults to `SessionStatus::Ok`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageBuilder;
+    /// let builder = MessageBuilder::new();
+    /// ```
+    pub fn new() -> MessageBuilder {
+        MessageBuilder::default()
+    }
+
+    /// Sets the auto id.
+    pub fn auto_id(mut self, auto_id: u32) -> MessageBuilder {
+        self.auto_id = auto_id;
+        self
+    }
+
+    /// Sets the IMEI, which must be fifteen ASCII digits.
+    pub fn imei(mut self, imei: &str) -> MessageBuilder {
+        self.imei = Some(imei.to_string());
+        self
+    }
+
+    /// Sets the session status.
+    pub fn session_status(mut self, session_status: SessionStatus) -> MessageBuilder {
+        self.session_status = Some(session_status);
+        self
+    }
+
+    /// Sets the mobile originated message sequence number.
+    pub fn momsn(mut self, momsn: u16) -> MessageBuilder {
+        self.momsn = momsn;
+        self
+    }
+
+    /// Sets the mobile terminated message sequence number.
+    pub fn mtmsn(mut self, mtmsn: u16) -> MessageBuilder {
+        self.mtmsn = mtmsn;
+        self
+    }
+
+    /// Sets the time of session.
+    pub fn time_of_session(mut self, time_of_session: DateTime<Utc>) -> MessageBuilder {
+        self.time_of_session = Some(time_of_session);
+        self
+    }
+
+    /// Sets the payload.
+    pub fn payload<P: Into<Vec<u8>>>(mut self, payload: P) -> MessageBuilder {
+        self.payload = payload.into();
+        self
+    }
+
+    /// Sets the raw location information.
+    pub fn location_information(mut self, location_information: [u8; 11]) -> MessageBuilder {
+        self.location_information = Some(location_information);
+        self
+    }
+
+    /// Sets the location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use sbd::mo::{MessageBuilder, MoLocation};
+    /// let location = MoLocation::new(40.015, -105.27, 3).unwrap();
+    /// let message = MessageBuilder::new()
+    ///     .imei("300234063904190")
+    ///     .time_of_session(Utc::now())
+    ///     .location(location)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(location, message.location().unwrap().unwrap());
+    /// ```
+    pub fn location(self, location: MoLocation) -> MessageBuilder {
+        self.location_information(location.to_bytes())
+    }
+
+    /// Builds the message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the IMEI or time of session are missing, if the IMEI is not fifteen
+    /// ASCII digits, if the time of session can't be represented as a 32-bit epoch time, if the
+    /// payload is longer than `MAX_PAYLOAD_LENGTH`, or if the message would be too long to
+    /// write.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use sbd::mo::MessageBuilder;
+    /// let builder = MessageBuilder::new().time_of_session(Utc::now());
+    /// assert!(builder.clone().build().is_err());
+    /// assert!(builder.clone().imei("not an imei").build().is_err());
+    /// assert!(builder.imei("300234063904190").build().is_ok());
+    /// ```
+    pub fn build(&self) -> Result<Message, Error> {
+        let imei: Imei = self
+            .imei
+            .as_ref()
+            .ok_or(Error::MissingField("imei"))?
+            .parse()?;
+
+        let time_of_session = self
+            .time_of_session
+            .ok_or(Error::MissingField("time_of_session"))?;
+        let timestamp = time_of_session.timestamp();
+        if timestamp < 0 {
+            return Err(Error::NegativeTimestamp(timestamp));
+        } else if timestamp > i64::from(u32::MAX) {
+            return Err(Error::InvalidTimeOfSession);
+        }
+
+        if self.payload.len() > MAX_PAYLOAD_LENGTH {
+            return Err(Error::PayloadTooLong(self.payload.len()));
+        }
+
+        let mut information_elements = vec![
+            InformationElement::Header(Header {
+                auto_id: self.auto_id,
+                imei,
+                session_status: self.session_status.unwrap_or(SessionStatus::Ok),
+                momsn: self.momsn,
+                mtmsn: self.mtmsn,
+                time_of_session,
+            }),
+            InformationElement::Payload(self.payload.clone()),
+        ];
+        if let Some(location_information) = self.location_information {
+            information_elements.push(InformationElement::LocationInformation(
+                location_information,
+            ));
+        }
+        let overall_message_length = information_elements
+            .iter()
+            .map(|ie| ie.len())
+            .sum::<usize>();
+        if overall_message_length > usize::from(u16::MAX) {
+            return Err(Error::OverallMessageLength(overall_message_length));
+        }
+        Message::new(information_elements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn builder() -> MessageBuilder {
+        MessageBuilder::new()
+            .imei("300234063904190")
+            .time_of_session(Utc.with_ymd_and_hms(2015, 7, 9, 18, 15, 8).unwrap())
+    }
+
+    #[test]
+    fn build() {
+        let message = builder()
+            .auto_id(1894516585)
+            .momsn(75)
+            .payload(b"test message from pete".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(Message::from_path("data/0-mo.sbd").unwrap(), message);
+    }
+
+    #[test]
+    fn location_information() {
+        let message = builder().location_information([0; 11]).build().unwrap();
+        assert_eq!(
+            &vec![InformationElement::LocationInformation([0; 11])],
+            message.information_elements()
+        );
+    }
+
+    #[test]
+    fn missing_fields() {
+        assert!(MessageBuilder::new()
+            .imei("300234063904190")
+            .build()
+            .is_err());
+        assert!(MessageBuilder::new()
+            .time_of_session(Utc::now())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_imei() {
+        assert!(builder().imei("30023406390419").build().is_err());
+        assert!(builder().imei("3002340639041900").build().is_err());
+        assert!(builder().imei("30023406390419a").build().is_err());
+    }
+
+    #[test]
+    fn invalid_time_of_session() {
+        assert!(builder()
+            .time_of_session(Utc.with_ymd_and_hms(1969, 12, 31, 23, 59, 59).unwrap())
+            .build()
+            .is_err());
+        assert!(builder()
+            .time_of_session(Utc.with_ymd_and_hms(2107, 1, 1, 0, 0, 0).unwrap())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn payload_too_long() {
+        assert!(builder()
+            .payload(vec![0; MAX_PAYLOAD_LENGTH])
+            .build()
+            .is_ok());
+        assert!(builder()
+            .payload(vec![0; MAX_PAYLOAD_LENGTH + 1])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn location() {
+        let location = MoLocation::new(-43.52117, 172.60487, 2).unwrap();
+        let message = builder().location(location).build().unwrap();
+        assert_eq!(location, message.location().unwrap().unwrap());
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        assert_eq!(
+            Some(Ok(location)),
+            Message::read_from(&bytes[..]).unwrap().location()
+        );
+    }
+}
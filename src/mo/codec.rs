@@ -0,0 +1,356 @@
+//! Read and write mobile-originated messages with tokio.
+
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    mo::{
+        message::MAX_TRAILING_BYTES, Message, MessageRef, ParseOptions, Policy,
+        PROTOCOL_REVISION_NUMBER,
+    },
+    Error,
+};
+
+/// A codec that frames a byte stream into mobile-originated messages.
+///
+/// Each frame is one message, delimited by its overall message length, and is checked just as
+/// `Message::read_from_with` would check it. The trailing bytes option is not used, since the
+/// bytes after one message are the next message.
+///
+/// # Examples
+///
+/// ```
+/// use bytes::BytesMut;
+/// use sbd::mo::MoCodec;
+/// use tokio_util::codec::Decoder;
+/// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+/// let mut buffer = BytesMut::from(&bytes[..10]);
+/// let mut codec = MoCodec::new();
+/// assert!(codec.decode(&mut buffer).unwrap().is_none());
+/// buffer.extend_from_slice(&bytes[10..]);
+/// let message = codec.decode(&mut buffer).unwrap().unwrap();
+/// assert_eq!(75, message.momsn());
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MoCodec {
+    options: ParseOptions,
+}
+
+impl MoCodec {
+    /// Creates a new codec, using the default `ParseOptions`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MoCodec;
+    /// let codec = MoCodec::new();
+    /// ```
+    pub fn new() -> MoCodec {
+        MoCodec::default()
+    }
+
+    /// Sets the options used to parse each message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{MoCodec, ParseOptions};
+    /// let codec = MoCodec::new().parse_options(ParseOptions::lenient());
+    /// ```
+    pub fn parse_options(mut self, options: ParseOptions) -> MoCodec {
+        self.options = options;
+        self
+    }
+}
+
+impl Decoder for MoCodec {
+    type Item = Message;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Message>, Error> {
+        if src.len() < 3 {
+            src.reserve(3 - src.len());
+            return Ok(None);
+        }
+        if src[0] != PROTOCOL_REVISION_NUMBER {
+            return Err(Error::InvalidProtocolRevisionNumber(src[0]));
+        }
+        let length = 3 + usize::from(BigEndian::read_u16(&src[1..3]));
+        if src.len() < length {
+            src.reserve(length - src.len());
+            return Ok(None);
+        }
+        let result = MessageRef::parse_with(&src[..length], &self.options).map(|m| m.to_message());
+        src.advance(length);
+        result.map(Some)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Message>, Error> {
+        match self.decode(src)? {
+            Some(message) => Ok(Some(message)),
+            None if src.is_empty() => Ok(None),
+            None => {
+                let needed = if src.len() < 3 {
+                    3
+                } else {
+                    3 + usize::from(BigEndian::read_u16(&src[1..3]))
+                };
+                Err(Error::UnexpectedEnd {
+                    needed,
+                    available: src.len(),
+                })
+            }
+        }
+    }
+}
+
+impl Encoder<&Message> for MoCodec {
+    type Error = Error;
+
+    fn encode(&mut self, message: &Message, dst: &mut BytesMut) -> Result<(), Error> {
+        let start = dst.len();
+        dst.resize(start + message.encoded_len(), 0);
+        if let Err(err) = message.encode_into(&mut dst[start..]) {
+            dst.truncate(start);
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl Encoder<Message> for MoCodec {
+    type Error = Error;
+
+    fn encode(&mut self, message: Message, dst: &mut BytesMut) -> Result<(), Error> {
+        self.encode(&message, dst)
+    }
+}
+
+impl Message {
+    /// Reads in a message from an `AsyncRead`.
+    ///
+    /// The message is checked just as `Message::read_from` checks it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// use sbd::mo::Message;
+    /// let file = tokio::fs::File::open("data/0-mo.sbd").await.unwrap();
+    /// let message = Message::read_from_async(file).await.unwrap();
+    /// # })
+    /// ```
+    pub async fn read_from_async<R: AsyncRead + Unpin>(read: R) -> Result<Message, Error> {
+        Message::read_from_async_with(read, &ParseOptions::default()).await
+    }
+
+    /// Reads in a message from an `AsyncRead`, using the provided `ParseOptions`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// use sbd::mo::{Message, ParseOptions};
+    /// let file = tokio::fs::File::open("data/0-mo.sbd").await.unwrap();
+    /// let message = Message::read_from_async_with(file, &ParseOptions::strict())
+    ///     .await
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub async fn read_from_async_with<R: AsyncRead + Unpin>(
+        mut read: R,
+        options: &ParseOptions,
+    ) -> Result<Message, Error> {
+        let protocol_revision_number = read.read_u8().await?;
+        if protocol_revision_number != PROTOCOL_REVISION_NUMBER {
+            return Err(Error::InvalidProtocolRevisionNumber(
+                protocol_revision_number,
+            ));
+        }
+        let overall_message_length = read.read_u16().await?;
+        let mut bytes = vec![protocol_revision_number, 0, 0];
+        BigEndian::write_u16(&mut bytes[1..3], overall_message_length);
+        let length = usize::from(overall_message_length);
+        (&mut read)
+            .take(length as u64)
+            .read_to_end(&mut bytes)
+            .await?;
+        if bytes.len() < 3 + length {
+            return Err(Error::UnexpectedEnd {
+                needed: length,
+                available: bytes.len() - 3,
+            });
+        }
+        let message = MessageRef::parse_with(&bytes, options)?.to_message();
+
+        if options.trailing_bytes != Policy::Ignore {
+            let trailing_bytes =
+                tokio::io::copy(&mut read.take(MAX_TRAILING_BYTES), &mut tokio::io::sink()).await?;
+            if trailing_bytes > 0 {
+                options
+                    .trailing_bytes
+                    .apply(Error::TrailingBytes(trailing_bytes as usize))?;
+            }
+        }
+
+        Ok(message)
+    }
+
+    /// Writes this message to an `AsyncWrite`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// use sbd::mo::Message;
+    /// let message = Message::from_path("data/0-mo.sbd").unwrap();
+    /// let mut bytes = Vec::new();
+    /// message.write_to_async(&mut bytes).await.unwrap();
+    /// assert_eq!(std::fs::read("data/0-mo.sbd").unwrap(), bytes);
+    /// # })
+    /// ```
+    pub async fn write_to_async<W: AsyncWrite + Unpin>(&self, mut write: W) -> Result<(), Error> {
+        let mut bytes = vec![0; self.encoded_len()];
+        let _ = self.encode_into(&mut bytes)?;
+        write.write_all(&bytes).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Framed;
+
+    use super::*;
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        bytes.extend(std::fs::read("data/2-location.mo.sbd").unwrap());
+        bytes
+    }
+
+    #[tokio::test]
+    async fn read_from_async() {
+        let bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        assert_eq!(
+            Message::read_from(&bytes[..]).unwrap(),
+            Message::read_from_async(&bytes[..]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn read_from_async_same_validation() {
+        let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        bytes.push(0);
+        assert!(Message::read_from_async(&bytes[..]).await.is_ok());
+        assert!(
+            Message::read_from_async_with(&bytes[..], &ParseOptions::strict())
+                .await
+                .is_err()
+        );
+        bytes.pop();
+        for i in 0..bytes.len() {
+            assert!(Message::read_from_async(&bytes[..i]).await.is_err());
+        }
+        bytes[10] = 0xff;
+        assert!(Message::read_from_async(&bytes[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn write_to_async() {
+        let message = Message::from_path("data/2-location.mo.sbd").unwrap();
+        let mut bytes = Vec::new();
+        message.write_to_async(&mut bytes).await.unwrap();
+        let mut expected = Vec::new();
+        message.write_to(&mut expected).unwrap();
+        assert_eq!(expected, bytes);
+    }
+
+    #[test]
+    fn decode_byte_by_byte() {
+        let mut codec = MoCodec::new();
+        let mut buffer = BytesMut::new();
+        let mut messages = Vec::new();
+        for byte in bytes() {
+            buffer.extend_from_slice(&[byte]);
+            if let Some(message) = codec.decode(&mut buffer).unwrap() {
+                messages.push(message);
+            }
+        }
+        assert!(buffer.is_empty());
+        assert_eq!(2, messages.len());
+        assert_eq!(75, messages[0].momsn());
+        assert_eq!(7, messages[1].momsn());
+        assert!(codec.decode_eof(&mut buffer).unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_eof_truncated() {
+        let bytes = bytes();
+        let mut buffer = BytesMut::from(&bytes[..bytes.len() - 1]);
+        let mut codec = MoCodec::new();
+        assert!(codec.decode_eof(&mut buffer).unwrap().is_some());
+        match codec.decode_eof(&mut buffer).unwrap_err() {
+            Error::UnexpectedEnd { needed, available } => {
+                assert_eq!(97, needed);
+                assert_eq!(96, available);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn decode_invalid() {
+        let mut bytes = bytes();
+        bytes[25] = 3; // unknown session status
+        let mut buffer = BytesMut::from(&bytes[..]);
+        let mut codec = MoCodec::new().parse_options(ParseOptions::strict());
+        assert!(codec.decode(&mut buffer).is_err());
+        assert_eq!(7, codec.decode(&mut buffer).unwrap().unwrap().momsn());
+
+        let mut buffer = BytesMut::from(&b"garbage"[..]);
+        assert!(codec.decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn encode() {
+        let message = Message::from_path("data/0-mo.sbd").unwrap();
+        let mut buffer = BytesMut::from(&b"x"[..]);
+        MoCodec::new().encode(&message, &mut buffer).unwrap();
+        assert_eq!(b"x", &buffer[..1]);
+        assert_eq!(std::fs::read("data/0-mo.sbd").unwrap(), &buffer[1..]);
+    }
+
+    #[tokio::test]
+    async fn framed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut framed = Framed::new(stream, MoCodec::new());
+            framed
+                .send(Message::from_path("data/0-mo.sbd").unwrap())
+                .await
+                .unwrap();
+            framed
+                .send(Message::from_path("data/2-location.mo.sbd").unwrap())
+                .await
+                .unwrap();
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let messages: Vec<Message> = Framed::new(stream, MoCodec::new())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        client.await.unwrap();
+        assert_eq!(2, messages.len());
+        assert_eq!(
+            Message::from_path("data/2-location.mo.sbd").unwrap(),
+            messages[1]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Serialize and deserialize mobile-originated messages with serde.
+//!
+//! A `Message` is serialized as an object with three fields:
+//!
+//! - `header`: the header, with the IMEI as a string and the time of session as an RFC 3339
+//!   timestamp
+//! - `payload`: an object with the payload `encoding` (`"base64"` or `"hex"`) and the encoded
+//!   `data`
+//! - `information_elements`: every other information element, e.g. `{"location_information":
+//!   [2, 43, 122, ...]}`
+//!
+//! For example:
+//!
+//! ```json
+//! {
+//!   "header": {
+//!     "auto_id": 1894516585,
+//!     "imei": "300234063904190",
+//!     "session_status": "Ok",
+//!     "momsn": 75,
+//!     "mtmsn": 0,
+//!     "time_of_session": "2015-07-09T18:15:08Z"
+//!   },
+//!   "payload": {
+//!     "encoding": "base64",
+//!     "data": "dGVzdCBtZXNzYWdlIGZyb20gcGV0ZQ=="
+//!   },
+//!   "information_elements": []
+//! }
+//! ```
+//!
+//! Payloads are base64-encoded by default. Use `Message::with_payload_encoding` to pick another
+//! encoding. Deserialization reads the encoding from the payload object, so any encoding can be
+//! read back into a `Message` that writes the same bytes.
+
+use alloc::{string::String, vec::Vec};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::mo::{Header, InformationElement, Message};
+
+/// How a message's payload is encoded when serialized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    /// Standard base64, with padding.
+    #[default]
+    Base64,
+    /// Lowercase hexadecimal.
+    Hex,
+}
+
+/// A message that serializes its payload with a specific encoding.
+///
+/// Created by `Message::with_payload_encoding`.
+#[derive(Clone, Copy, Debug)]
+pub struct WithPayloadEncoding<'a> {
+    message: &'a Message,
+    encoding: PayloadEncoding,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    encoding: PayloadEncoding,
+    data: &'a str,
+}
+
+#[derive(Deserialize)]
+struct OwnedPayload {
+    encoding: PayloadEncoding,
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct OwnedMessage {
+    header: Header,
+    payload: OwnedPayload,
+    #[serde(default)]
+    information_elements: Vec<InformationElement>,
+}
+
+impl Message {
+    /// Returns a wrapper around this message that serializes the payload with the given
+    /// encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{Message, PayloadEncoding};
+    /// let message = Message::from_path("data/0-mo.sbd").unwrap();
+    /// let json = serde_json::to_value(message.with_payload_encoding(PayloadEncoding::Hex)).unwrap();
+    /// assert_eq!("hex", json["payload"]["encoding"]);
+    /// ```
+    pub fn with_payload_encoding(&self, encoding: PayloadEncoding) -> WithPayloadEncoding<'_> {
+        WithPayloadEncoding {
+            message: self,
+            encoding,
+        }
+    }
+}
+
+impl PayloadEncoding {
+    fn encode(&self, bytes: &[u8]) -> String {
+        match *self {
+            PayloadEncoding::Base64 => STANDARD.encode(bytes),
+            PayloadEncoding::Hex => hex::encode(bytes),
+        }
+    }
+
+    fn decode(&self, s: &str) -> Option<Vec<u8>> {
+        match *self {
+            PayloadEncoding::Base64 => STANDARD.decode(s).ok(),
+            PayloadEncoding::Hex => hex::decode(s).ok(),
+        }
+    }
+}
+
+impl Serialize for WithPayloadEncoding<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = self.encoding.encode(self.message.payload());
+        let mut state = serializer.serialize_struct("Message", 3)?;
+        state.serialize_field("header", self.message.header())?;
+        state.serialize_field(
+            "payload",
+            &Payload {
+                encoding: self.encoding,
+                data: &data,
+            },
+        )?;
+        state.serialize_field("information_elements", self.message.information_elements())?;
+        state.end()
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.with_payload_encoding(PayloadEncoding::default())
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Message, D::Error> {
+        let message = OwnedMessage::deserialize(deserializer)?;
+        let payload = message
+            .payload
+            .encoding
+            .decode(&message.payload.data)
+            .ok_or_else(|| {
+                de::Error::invalid_value(
+                    de::Unexpected::Str(&message.payload.data),
+                    &"an encoded payload",
+                )
+            })?;
+        Message::new(
+            core::iter::once(InformationElement::Header(message.header))
+                .chain(core::iter::once(InformationElement::Payload(payload)))
+                .chain(message.information_elements),
+        )
+        .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(path: &str, encoding: PayloadEncoding) {
+        let message = Message::from_path(path).unwrap();
+        let json = serde_json::to_string(&message.with_payload_encoding(encoding)).unwrap();
+        let message2: Message = serde_json::from_str(&json).unwrap();
+        assert_eq!(message, message2);
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        let mut bytes2 = Vec::new();
+        message2.write_to(&mut bytes2).unwrap();
+        assert_eq!(bytes, bytes2);
+    }
+
+    #[test]
+    fn roundtrip_base64() {
+        roundtrip("data/0-mo.sbd", PayloadEncoding::Base64);
+        roundtrip("data/2-location.mo.sbd", PayloadEncoding::Base64);
+    }
+
+    #[test]
+    fn roundtrip_hex() {
+        roundtrip("data/0-mo.sbd", PayloadEncoding::Hex);
+        roundtrip("data/2-location.mo.sbd", PayloadEncoding::Hex);
+    }
+
+    #[test]
+    fn representation() {
+        let message = Message::from_path("data/0-mo.sbd").unwrap();
+        let value = serde_json::to_value(&message).unwrap();
+        assert_eq!("300234063904190", value["header"]["imei"]);
+        assert_eq!("Ok", value["header"]["session_status"]);
+        assert_eq!("2015-07-09T18:15:08Z", value["header"]["time_of_session"]);
+        assert_eq!("base64", value["payload"]["encoding"]);
+        assert_eq!("dGVzdCBtZXNzYWdlIGZyb20gcGV0ZQ==", value["payload"]["data"]);
+        assert!(value["information_elements"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn hex_payload() {
+        let message = Message::from_path("data/0-mo.sbd").unwrap();
+        let value =
+            serde_json::to_value(message.with_payload_encoding(PayloadEncoding::Hex)).unwrap();
+        assert_eq!(
+            "74657374206d6573736167652066726f6d2070657465",
+            value["payload"]["data"]
+        );
+    }
+
+    #[test]
+    fn invalid_payload() {
+        let message = Message::from_path("data/0-mo.sbd").unwrap();
+        let mut value = serde_json::to_value(&message).unwrap();
+        value["payload"]["data"] = "not base64!".into();
+        assert!(serde_json::from_value::<Message>(value).is_err());
+    }
+}
@@ -0,0 +1,493 @@
+//! Annotated, byte-by-byte explanations of mobile-originated messages.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use byteorder::{BigEndian, ByteOrder};
+use chrono::{TimeZone, Utc};
+
+use crate::{
+    mo::{
+        information_element::body_length, InformationElement, Message, MoLocation, ParseOptions,
+        SessionStatus, PROTOCOL_REVISION_NUMBER,
+    },
+    Error,
+};
+
+/// One annotated span of bytes in a message.
+///
+/// Annotations form a tree: the root covers the whole input, its children are the protocol
+/// revision number, the overall message length, and each information element, and each
+/// information element's children are its identifier, length, and fields. An annotation that
+/// couldn't be parsed carries the error, and anything after it is left out.
+///
+/// The `Display` implementation renders the tree as indented text, one span per line, starting
+/// with the byte offset and length. With the `serde` feature, annotations can also be serialized,
+/// e.g. to JSON.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mo::Message;
+/// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+/// let annotation = Message::explain(&bytes);
+/// assert!(!annotation.has_errors());
+/// assert_eq!("protocol revision number", annotation.children[0].label);
+/// println!("{}", annotation);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Annotation {
+    /// The offset of the first byte of this span from the start of the input.
+    pub offset: usize,
+    /// The number of bytes in this span.
+    pub length: usize,
+    /// What this span is.
+    pub label: String,
+    /// The decoded value of this span, if it has one.
+    pub value: Option<String>,
+    /// The error encountered while parsing this span, if any.
+    pub error: Option<String>,
+    /// The spans that make up this span.
+    pub children: Vec<Annotation>,
+}
+
+impl Message {
+    /// Explains the layout of a message in a byte slice, using the default `ParseOptions`.
+    ///
+    /// Unlike `Message::read_from`, this never fails. Problems are recorded on the annotation
+    /// where they were found, along with everything that could be parsed before them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let annotation = Message::explain(&bytes[..40]);
+    /// assert!(annotation.has_errors());
+    /// assert_eq!(4, annotation.children.len());
+    /// ```
+    pub fn explain(bytes: &[u8]) -> Annotation {
+        Message::explain_with(bytes, &ParseOptions::default())
+    }
+
+    /// Explains the layout of a message in a byte slice, using the provided `ParseOptions`.
+    ///
+    /// The bytes are walked with the same rules as `Message::read_from_with`, so an anomaly is
+    /// recorded as an error exactly when reading the message would fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{Message, ParseOptions};
+    /// let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// bytes.push(0);
+    /// assert!(!Message::explain(&bytes).has_errors());
+    /// assert!(Message::explain_with(&bytes, &ParseOptions::strict()).has_errors());
+    /// ```
+    pub fn explain_with(bytes: &[u8], options: &ParseOptions) -> Annotation {
+        let mut root = Annotation::new(0, bytes.len(), "message");
+        if let Err(err) = explain_message(bytes, options, &mut root) {
+            root.error = Some(err.to_string());
+        }
+        root
+    }
+}
+
+impl Annotation {
+    fn new<S: Into<String>>(offset: usize, length: usize, label: S) -> Annotation {
+        Annotation {
+            offset,
+            length,
+            label: label.into(),
+            value: None,
+            error: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn value<T: ToString>(mut self, value: T) -> Annotation {
+        self.value = Some(value.to_string());
+        self
+    }
+
+    /// Returns true if this annotation, or any of its descendants, has an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// assert!(Message::explain(&[1, 0]).has_errors());
+    /// ```
+    pub fn has_errors(&self) -> bool {
+        self.error.is_some() || self.children.iter().any(Annotation::has_errors)
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(
+            f,
+            "{:>6} {:>6}  {:indent$}{}",
+            self.offset,
+            self.length,
+            "",
+            self.label,
+            indent = 2 * depth
+        )?;
+        if let Some(ref value) = self.value {
+            write!(f, ": {}", value)?;
+        }
+        if let Some(ref error) = self.error {
+            write!(f, " [error: {}]", error)?;
+        }
+        writeln!(f)?;
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>6} {:>6}", "offset", "length")?;
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Walks the message, pushing annotations onto `root` as it goes.
+///
+/// An error returned from here belongs to the message as a whole. Errors in an information
+/// element are recorded on that element's annotation, and stop the walk.
+fn explain_message(
+    bytes: &[u8],
+    options: &ParseOptions,
+    root: &mut Annotation,
+) -> Result<(), Error> {
+    let protocol_revision_number = *bytes.first().ok_or(Error::UnexpectedEnd {
+        needed: 3,
+        available: 0,
+    })?;
+    let mut annotation =
+        Annotation::new(0, 1, "protocol revision number").value(protocol_revision_number);
+    if protocol_revision_number != PROTOCOL_REVISION_NUMBER {
+        annotation.error =
+            Some(Error::InvalidProtocolRevisionNumber(protocol_revision_number).to_string());
+    }
+    root.children.push(annotation);
+    if bytes.len() < 3 {
+        return Err(Error::UnexpectedEnd {
+            needed: 3,
+            available: bytes.len(),
+        });
+    }
+    let overall_message_length = BigEndian::read_u16(&bytes[1..3]);
+    root.children
+        .push(Annotation::new(1, 2, "overall message length").value(overall_message_length));
+    if protocol_revision_number != PROTOCOL_REVISION_NUMBER {
+        return Ok(());
+    }
+
+    let length = 3 + usize::from(overall_message_length);
+    let end = length.min(bytes.len());
+    let mut headers = Vec::new();
+    let mut payloads = Vec::new();
+    let mut offset = 3;
+    while offset < end {
+        let (annotation, information_element) =
+            explain_information_element(&bytes[..end], offset, options);
+        let failed = annotation.error.is_some();
+        offset += annotation.length;
+        root.children.push(annotation);
+        if failed {
+            return Ok(());
+        }
+        match information_element {
+            Some(InformationElement::Header(header)) => headers.push(header),
+            Some(InformationElement::Payload(payload)) => payloads.push(payload),
+            _ => {}
+        }
+    }
+
+    if bytes.len() < length {
+        return Err(Error::UnexpectedEnd {
+            needed: length,
+            available: bytes.len(),
+        });
+    }
+    if bytes.len() > length {
+        let mut annotation = Annotation::new(length, bytes.len() - length, "trailing bytes");
+        if let Err(err) = options
+            .trailing_bytes
+            .apply(Error::TrailingBytes(bytes.len() - length))
+        {
+            annotation.error = Some(err.to_string());
+        }
+        root.children.push(annotation);
+    }
+    if headers.len() > 1 {
+        options
+            .duplicate_information_elements
+            .apply(Error::TwoHeaders(headers[1], headers[0]))?;
+    }
+    if payloads.len() > 1 {
+        options
+            .duplicate_information_elements
+            .apply(Error::TwoPayloads(
+                payloads.swap_remove(1),
+                payloads.swap_remove(0),
+            ))?;
+    }
+    if headers.is_empty() {
+        return Err(Error::NoHeader);
+    }
+    if payloads.is_empty() {
+        return Err(Error::NoPayload);
+    }
+    Ok(())
+}
+
+/// Explains the information element at `offset`, decoding it if possible.
+fn explain_information_element(
+    bytes: &[u8],
+    offset: usize,
+    options: &ParseOptions,
+) -> (Annotation, Option<InformationElement>) {
+    let iei = bytes[offset];
+    let available = bytes.len() - offset;
+    let mut annotation = Annotation::new(offset, available.min(3), information_element_name(iei));
+    annotation
+        .children
+        .push(Annotation::new(offset, 1, "identifier").value(iei));
+    if available < 3 {
+        annotation.error = Some(
+            Error::UnexpectedEnd {
+                needed: 3,
+                available,
+            }
+            .to_string(),
+        );
+        return (annotation, None);
+    }
+    let declared = BigEndian::read_u16(&bytes[offset + 1..offset + 3]);
+    annotation
+        .children
+        .push(Annotation::new(offset + 1, 2, "length").value(declared));
+    let length = match body_length(iei, declared, options) {
+        Ok(length) => length,
+        Err(err) => {
+            annotation.error = Some(err.to_string());
+            return (annotation, None);
+        }
+    };
+    let body = &bytes[offset + 3..bytes.len().min(offset + 3 + length)];
+    annotation.length = 3 + body.len();
+    annotation
+        .children
+        .extend(explain_fields(iei, offset + 3, body));
+    if body.len() < length {
+        annotation.error = Some(
+            Error::UnexpectedEnd {
+                needed: length,
+                available: body.len(),
+            }
+            .to_string(),
+        );
+        return (annotation, None);
+    }
+    match InformationElement::decode(iei, body, options) {
+        Ok(information_element) => (annotation, Some(information_element)),
+        Err(err) => {
+            annotation.error = Some(err.to_string());
+            (annotation, None)
+        }
+    }
+}
+
+fn information_element_name(iei: u8) -> String {
+    match iei {
+        1 => "header".to_string(),
+        2 => "payload".to_string(),
+        3 => "location information".to_string(),
+        5 => "confirmation".to_string(),
+        _ => format!("unknown information element {}", iei),
+    }
+}
+
+/// Annotates the fields of an information element body that starts at `offset`.
+///
+/// The body may be truncated, in which case only the complete fields are annotated.
+fn explain_fields(iei: u8, offset: usize, body: &[u8]) -> Vec<Annotation> {
+    let field = |start: usize, length: usize, label: &str| {
+        body.get(start..start + length)
+            .map(|bytes| (Annotation::new(offset + start, length, label), bytes))
+    };
+    let mut fields = Vec::new();
+    match iei {
+        1 => {
+            fields.extend(field(0, 4, "auto id").map(|(a, b)| a.value(BigEndian::read_u32(b))));
+            fields.extend(field(4, 15, "imei").map(|(a, b)| a.value(String::from_utf8_lossy(b))));
+            fields.extend(field(19, 1, "session status").map(|(a, b)| {
+                let session_status = SessionStatus::from(b[0]);
+                a.value(format!("{} ({:?})", b[0], session_status))
+            }));
+            fields.extend(field(20, 2, "momsn").map(|(a, b)| a.value(BigEndian::read_u16(b))));
+            fields.extend(field(22, 2, "mtmsn").map(|(a, b)| a.value(BigEndian::read_u16(b))));
+            fields.extend(field(24, 4, "time of session").map(|(a, b)| {
+                let timestamp = BigEndian::read_u32(b);
+                match Utc.timestamp_opt(i64::from(timestamp), 0).single() {
+                    Some(time) => a.value(time.to_rfc3339()),
+                    None => a.value(timestamp),
+                }
+            }));
+        }
+        2 => {
+            let mut annotation = Annotation::new(offset, body.len(), "data");
+            if let Ok(s) = core::str::from_utf8(body) {
+                annotation = annotation.value(format!("{:?}", s));
+            }
+            fields.push(annotation);
+        }
+        3 => {
+            fields.extend(field(0, 1, "flags").map(|(a, b)| a.value(format!("{:#010b}", b[0]))));
+            fields.extend(field(1, 1, "latitude degrees").map(|(a, b)| a.value(b[0])));
+            fields.extend(
+                field(2, 2, "latitude thousandths of a minute")
+                    .map(|(a, b)| a.value(BigEndian::read_u16(b))),
+            );
+            fields.extend(field(4, 1, "longitude degrees").map(|(a, b)| a.value(b[0])));
+            fields.extend(
+                field(5, 2, "longitude thousandths of a minute")
+                    .map(|(a, b)| a.value(BigEndian::read_u16(b))),
+            );
+            fields.extend(
+                field(7, 4, "cep radius (km)").map(|(a, b)| a.value(BigEndian::read_u32(b))),
+            );
+            if let Ok(bytes) = <[u8; 11]>::try_from(body) {
+                if let Ok(location) = MoLocation::parse(bytes) {
+                    let position = format!(
+                        "{:.5}, {:.5}",
+                        location.latitude_deg(),
+                        location.longitude_deg()
+                    );
+                    fields.insert(0, Annotation::new(offset, 11, "position").value(position));
+                }
+            }
+        }
+        5 => {
+            fields.extend(field(0, 1, "status").map(|(a, b)| a.value(b[0])));
+        }
+        _ => {
+            fields.push(Annotation::new(offset, body.len(), "data"));
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(annotation: &Annotation) -> Vec<&str> {
+        annotation
+            .children
+            .iter()
+            .map(|child| child.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn explain() {
+        let bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        let annotation = Message::explain(&bytes);
+        assert!(!annotation.has_errors());
+        assert_eq!(bytes.len(), annotation.length);
+        assert_eq!(
+            vec![
+                "protocol revision number",
+                "overall message length",
+                "header",
+                "location information",
+                "payload"
+            ],
+            labels(&annotation)
+        );
+        let header = &annotation.children[2];
+        assert_eq!(3, header.offset);
+        assert_eq!(31, header.length);
+        let imei = header.children.iter().find(|a| a.label == "imei").unwrap();
+        assert_eq!(Some("301434061799480"), imei.value.as_deref());
+        assert_eq!(10, imei.offset);
+        let location = &annotation.children[3];
+        assert_eq!(34, location.offset);
+        assert_eq!(14, location.length);
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        for i in 0..bytes.len() {
+            let annotation = Message::explain(&bytes[..i]);
+            assert!(annotation.has_errors(), "{}", i);
+            let end = annotation
+                .children
+                .last()
+                .map(|child| child.offset + child.length)
+                .unwrap_or(0);
+            assert!(end <= i, "{}", i);
+        }
+    }
+
+    #[test]
+    fn invalid_information_element_length() {
+        let mut bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        bytes[2] += 1;
+        bytes[36] = 12;
+        bytes.insert(48, 0);
+        let annotation = Message::explain(&bytes);
+        assert_eq!(None, annotation.error);
+        let location = annotation.children.last().unwrap();
+        assert_eq!("location information", location.label);
+        assert_eq!(34, location.offset);
+        assert!(location.error.is_some());
+        assert!(!Message::explain_with(&bytes, &ParseOptions::lenient()).has_errors());
+    }
+
+    #[test]
+    fn agrees_with_read_from() {
+        let bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        for i in 0..bytes.len() {
+            for value in [0x00, 0x01, 0x03, 0x0b, 0xff] {
+                let mut bytes = bytes.clone();
+                bytes[i] = value;
+                assert_eq!(
+                    Message::read_from(&bytes[..]).is_err(),
+                    Message::explain(&bytes).has_errors(),
+                    "byte {} = {}",
+                    i,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn display() {
+        let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        let text = Message::explain(&bytes).to_string();
+        assert!(text.contains("     3     31    header\n"));
+        assert!(text.contains("     0      1    protocol revision number: 1\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() {
+        let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        let value = serde_json::to_value(Message::explain(&bytes)).unwrap();
+        assert_eq!("header", value["children"][2]["label"]);
+        assert_eq!(3, value["children"][2]["offset"]);
+    }
+}
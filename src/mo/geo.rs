@@ -0,0 +1,248 @@
+//! Geometry on the locations reported with mobile-originated messages.
+//!
+//! Distances and bearings use a spherical Earth, which is well within the precision of an Iridium
+//! location fix.
+
+use crate::mo::{Header, Message, MoLocation, MoLocationError};
+
+/// The mean radius of the Earth, in kilometers.
+pub const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// A GeoJSON feature for a message with a location.
+///
+/// With the `serde` feature, this serializes to a GeoJSON `Feature` with a `Point` geometry. The
+/// properties are the header fields, with the IMEI as a string and the time of session in RFC
+/// 3339, plus the `cep_km` of the location.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mo::Message;
+/// let message = Message::from_path("data/2-location.mo.sbd").unwrap();
+/// let feature = message.to_feature().unwrap().unwrap();
+/// # #[cfg(feature = "serde")] {
+/// let value = serde_json::to_value(&feature).unwrap();
+/// assert_eq!("Feature", value["type"]);
+/// assert_eq!("301434061799480", value["properties"]["imei"]);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Feature {
+    /// The header of the message.
+    pub header: Header,
+    /// The location of the message.
+    pub location: MoLocation,
+}
+
+impl MoLocation {
+    /// Returns the great-circle distance to another location, in kilometers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MoLocation;
+    /// let boulder = MoLocation::new(40.015, -105.27, 1).unwrap();
+    /// let denver = MoLocation::new(39.7392, -104.9903, 1).unwrap();
+    /// assert_eq!(39, boulder.distance_km(&denver).round() as i64);
+    /// ```
+    pub fn distance_km(&self, other: &MoLocation) -> f64 {
+        let (lat1, lon1) = self.radians();
+        let (lat2, lon2) = other.radians();
+        let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
+
+    /// Returns the initial bearing to another location, in degrees clockwise from north.
+    ///
+    /// The bearing is in `0.0..360.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MoLocation;
+    /// let origin = MoLocation::new(0.0, 0.0, 1).unwrap();
+    /// let east = MoLocation::new(0.0, 1.0, 1).unwrap();
+    /// assert_eq!(90.0, origin.bearing_deg(&east).round());
+    /// ```
+    pub fn bearing_deg(&self, other: &MoLocation) -> f64 {
+        let (lat1, lon1) = self.radians();
+        let (lat2, lon2) = other.radians();
+        let y = (lon2 - lon1).sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * (lon2 - lon1).cos();
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+
+    /// Returns the circular error probable as a closed polygon with `vertices` vertices.
+    ///
+    /// Each point is `[longitude, latitude]` in decimal degrees, as in GeoJSON, and the last point
+    /// repeats the first. Longitudes are wrapped into -180..=180, but the polygon isn't split at
+    /// the antimeridian.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MoLocation;
+    /// let location = MoLocation::new(40.015, -105.27, 5).unwrap();
+    /// let polygon = location.cep_polygon(32);
+    /// assert_eq!(33, polygon.len());
+    /// assert_eq!(polygon[0], polygon[32]);
+    /// ```
+    pub fn cep_polygon(&self, vertices: usize) -> Vec<[f64; 2]> {
+        let (lat, lon) = self.radians();
+        let angular_distance = f64::from(self.cep_km) / EARTH_RADIUS_KM;
+        let mut polygon: Vec<[f64; 2]> = (0..vertices)
+            .map(|i| {
+                let bearing = 2.0 * std::f64::consts::PI * i as f64 / vertices as f64;
+                let lat2 = (lat.sin() * angular_distance.cos()
+                    + lat.cos() * angular_distance.sin() * bearing.cos())
+                .asin();
+                let lon2 = lon
+                    + (bearing.sin() * angular_distance.sin() * lat.cos())
+                        .atan2(angular_distance.cos() - lat.sin() * lat2.sin());
+                [
+                    (lon2.to_degrees() + 540.0).rem_euclid(360.0) - 180.0,
+                    lat2.to_degrees(),
+                ]
+            })
+            .collect();
+        if let Some(&first) = polygon.first() {
+            polygon.push(first);
+        }
+        polygon
+    }
+
+    fn radians(&self) -> (f64, f64) {
+        (
+            self.latitude_deg().to_radians(),
+            self.longitude_deg().to_radians(),
+        )
+    }
+}
+
+impl Message {
+    /// Returns a GeoJSON feature for this message, if it has a location information element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// let message = Message::from_path("data/2-location.mo.sbd").unwrap();
+    /// let feature = message.to_feature().unwrap().unwrap();
+    /// assert_eq!(2, feature.location.cep_km);
+    /// assert!(Message::from_path("data/0-mo.sbd").unwrap().to_feature().is_none());
+    /// ```
+    pub fn to_feature(&self) -> Option<Result<Feature, MoLocationError>> {
+        self.location().map(|location| {
+            location.map(|location| Feature {
+                header: *self.header(),
+                location,
+            })
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Feature {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        #[derive(serde::Serialize)]
+        struct Point {
+            r#type: &'static str,
+            coordinates: [f64; 2],
+        }
+
+        #[derive(serde::Serialize)]
+        struct Properties<'a> {
+            #[serde(flatten)]
+            header: &'a Header,
+            cep_km: u32,
+        }
+
+        let mut state = serializer.serialize_struct("Feature", 3)?;
+        state.serialize_field("type", "Feature")?;
+        state.serialize_field(
+            "geometry",
+            &Point {
+                r#type: "Point",
+                coordinates: [self.location.longitude_deg(), self.location.latitude_deg()],
+            },
+        )?;
+        state.serialize_field(
+            "properties",
+            &Properties {
+                header: &self.header,
+                cep_km: self.location.cep_km,
+            },
+        )?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(latitude: f64, longitude: f64) -> MoLocation {
+        MoLocation::new(latitude, longitude, 10).unwrap()
+    }
+
+    #[test]
+    fn distance() {
+        let origin = location(0.0, 0.0);
+        assert_eq!(0.0, origin.distance_km(&origin));
+        let one_degree = EARTH_RADIUS_KM * std::f64::consts::PI / 180.0;
+        assert!((origin.distance_km(&location(1.0, 0.0)) - one_degree).abs() < 1e-3);
+        assert!((origin.distance_km(&location(0.0, -1.0)) - one_degree).abs() < 1e-3);
+        let antipode = location(0.0, 180.0);
+        assert!((origin.distance_km(&antipode) - one_degree * 180.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn bearing() {
+        let origin = location(0.0, 0.0);
+        assert!((origin.bearing_deg(&location(1.0, 0.0)) - 0.0).abs() < 1e-9);
+        assert!((origin.bearing_deg(&location(0.0, 1.0)) - 90.0).abs() < 1e-9);
+        assert!((origin.bearing_deg(&location(-1.0, 0.0)) - 180.0).abs() < 1e-9);
+        assert!((origin.bearing_deg(&location(0.0, -1.0)) - 270.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cep_polygon() {
+        let center = location(-43.52117, 172.60487);
+        let polygon = center.cep_polygon(16);
+        assert_eq!(17, polygon.len());
+        assert_eq!(polygon[0], polygon[16]);
+        for [longitude, latitude] in polygon {
+            let vertex = MoLocation::new(latitude, longitude, 0).unwrap();
+            // Vertices are rounded to a thousandth of a minute, about two meters.
+            assert!((center.distance_km(&vertex) - 10.0).abs() < 0.005);
+        }
+        assert!(center.cep_polygon(0).is_empty());
+    }
+
+    #[test]
+    fn cep_polygon_antimeridian() {
+        let polygon = location(0.0, 179.99).cep_polygon(4);
+        assert!(polygon[1][0] < -179.9);
+        assert!(polygon[3][0] < 180.0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn geojson() {
+        let message = Message::from_path("data/2-location.mo.sbd").unwrap();
+        let value = serde_json::to_value(message.to_feature().unwrap().unwrap()).unwrap();
+        assert_eq!("Point", value["geometry"]["type"]);
+        let coordinates = value["geometry"]["coordinates"].as_array().unwrap();
+        assert!((coordinates[0].as_f64().unwrap() - 172.60487).abs() < 1e-5);
+        assert!((coordinates[1].as_f64().unwrap() + 43.52117).abs() < 1e-5);
+        assert_eq!(7, value["properties"]["momsn"]);
+        assert_eq!(2, value["properties"]["cep_km"]);
+        assert_eq!(
+            "2025-09-14T23:30:40Z",
+            value["properties"]["time_of_session"]
+        );
+    }
+}
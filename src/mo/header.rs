@@ -1,14 +1,15 @@
 use chrono::{DateTime, Utc};
 
-use crate::mo::SessionStatus;
+use crate::{mo::SessionStatus, Imei};
 
 /// A mobile-originated header.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     /// The Iridium Gateway id for this message.
     pub auto_id: u32,
     /// The device id.
-    pub imei: [u8; 15],
+    pub imei: Imei,
     /// The session status.
     pub session_status: SessionStatus,
     /// The mobile originated message sequence number.
@@ -3,23 +3,32 @@
 //! Information elements come after the SBD header. They come in many types,
 //! including more header-type information and the actual data payload.
 
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::io::{Read, Write};
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+use byteorder::ReadBytesExt;
+use byteorder::{BigEndian, ByteOrder};
 use chrono::{TimeZone, Utc};
 
 use crate::{
     mo::{
         location::{MoLocation, MoLocationError},
-        Header, SessionStatus,
+        Header, ParseOptions, Policy, SessionStatus,
     },
-    Error,
+    Error, Imei,
 };
 
 /// A mobile-originated information element, or IE.
 ///
 /// These are the building blocks of a SBD message.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum InformationElement {
     /// Information element holding the mobile-originated header.
     Header(Header),
@@ -27,50 +36,82 @@ pub enum InformationElement {
     Payload(Vec<u8>),
     /// The mobile originated location information.
     LocationInformation([u8; 11]),
+    /// The confirmation sent from the vendor server back to the Iridium Gateway.
+    ///
+    /// `true` indicates success, `false` indicates failure.
+    Confirmation(bool),
+    /// An information element that this library does not know how to interpret.
+    ///
+    /// The raw bytes are preserved so the element can be written back unchanged.
+    Unknown {
+        /// The information element identifier.
+        iei: u8,
+        /// The contents of the information element, not including the identifier or length.
+        bytes: Vec<u8>,
+    },
 }
 
 impl InformationElement {
     /// Reads this information element from a `Read`.
-    pub fn read_from<R: Read>(mut read: R) -> Result<InformationElement, Error> {
+    ///
+    /// Uses the default `ParseOptions`.
+    #[cfg(feature = "std")]
+    pub fn read_from<R: Read>(read: R) -> Result<InformationElement, Error> {
+        InformationElement::read_from_with(read, &ParseOptions::default())
+    }
+
+    /// Reads this information element from a `Read`, using the provided `ParseOptions`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{InformationElement, ParseOptions};
+    /// // A location information element that declares the wrong length.
+    /// let bytes = [3, 0, 12, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0];
+    /// assert!(InformationElement::read_from_with(&bytes[..], &ParseOptions::strict()).is_err());
+    /// assert!(InformationElement::read_from_with(&bytes[..], &ParseOptions::lenient()).is_ok());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn read_from_with<R: Read>(
+        mut read: R,
+        options: &ParseOptions,
+    ) -> Result<InformationElement, Error> {
         let iei = read.read_u8()?;
         let length = read.read_u16::<BigEndian>()?;
+        let mut bytes = Vec::new();
+        read_onto(read, body_length(iei, length, options)?, &mut bytes)?;
+        InformationElement::decode(iei, &bytes, options)
+    }
+
+    /// Decodes an information element from its identifier and the bytes after its length field.
+    ///
+    /// `bytes` should already be sized with `body_length`.
+    pub(crate) fn decode(
+        iei: u8,
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<InformationElement, Error> {
         match iei {
-            1 => {
-                let auto_id = read.read_u32::<BigEndian>()?;
-                let mut imei = [0; 15];
-                read.read_exact(&mut imei)?;
-                let session_status = SessionStatus::new(read.read_u8()?)?;
-                let momsn = read.read_u16::<BigEndian>()?;
-                let mtmsn = read.read_u16::<BigEndian>()?;
-                let time_of_session =
-                    read.read_u32::<BigEndian>()
-                        .map_err(Error::from)
-                        .and_then(|n| {
-                            Utc.timestamp_opt(i64::from(n), 0)
-                                .single()
-                                .ok_or(Error::InvalidTimeOfSession)
-                        })?;
-                Ok(InformationElement::Header(Header {
-                    auto_id,
-                    imei,
-                    session_status,
-                    momsn,
-                    mtmsn,
-                    time_of_session,
-                }))
-            }
-            2 => {
-                let mut payload = vec![0; length as usize];
-                read.read_exact(&mut payload)?;
-                Ok(InformationElement::Payload(payload))
-            }
+            1 => decode_header(bytes, options).map(InformationElement::Header),
+            2 => Ok(InformationElement::Payload(bytes.to_vec())),
             3 => {
-                let mut bytes = [0; 11];
-                read.read_exact(&mut bytes)?;
+                let bytes: [u8; 11] = fixed(bytes)?;
+                if options.invalid_location != Policy::Ignore {
+                    if let Err(err) = MoLocation::parse(bytes) {
+                        options.invalid_location.apply(err.into())?;
+                    }
+                }
                 Ok(InformationElement::LocationInformation(bytes))
             }
-            5 => unimplemented!(),
-            _ => Err(Error::InvalidInformationElementIdentifier(iei)),
+            5 => match fixed::<1>(bytes)? {
+                [0] => Ok(InformationElement::Confirmation(false)),
+                [1] => Ok(InformationElement::Confirmation(true)),
+                [n] => Err(Error::InvalidConfirmationStatus(n)),
+            },
+            _ => Ok(InformationElement::Unknown {
+                iei,
+                bytes: bytes.to_vec(),
+            }),
         }
     }
 
@@ -80,55 +121,80 @@ impl InformationElement {
             InformationElement::Header(_) => 31,
             InformationElement::Payload(ref payload) => 3 + payload.len(),
             InformationElement::LocationInformation(_) => 14,
+            InformationElement::Confirmation(_) => 4,
+            InformationElement::Unknown { ref bytes, .. } => 3 + bytes.len(),
         }
     }
 
     /// Returns true if this information element is empty.
     ///
-    /// At this point, only can be true if the payload is empty.
+    /// At this point, only can be true if the payload or an unknown element is empty.
     pub fn is_empty(&self) -> bool {
         match *self {
             InformationElement::Payload(ref payload) => payload.is_empty(),
+            InformationElement::Unknown { ref bytes, .. } => bytes.is_empty(),
             _ => false,
         }
     }
 
     /// Writes this information element to a `Write`.
+    #[cfg(feature = "std")]
     pub fn write_to<W: Write>(&self, mut write: W) -> Result<(), Error> {
-        match *self {
+        let mut bytes = vec![0; self.len()];
+        let _ = self.encode_into(&mut bytes)?;
+        write.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Encodes this information element into the start of a byte slice.
+    ///
+    /// Returns the number of bytes written, which is always `len()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the information element can't be encoded (e.g. the payload is too
+    /// long) or if the buffer is too small.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::InformationElement;
+    /// let mut buffer = [0; 8];
+    /// let n = InformationElement::Confirmation(true).encode_into(&mut buffer).unwrap();
+    /// assert_eq!(&[5, 0, 1, 1], &buffer[..n]);
+    /// ```
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut fixed = [0; 28];
+        let (iei, body): (u8, &[u8]) = match *self {
             InformationElement::Header(ref header) => {
-                write.write_u8(1)?;
-                write.write_u16::<BigEndian>(28)?;
-                write.write_u32::<BigEndian>(header.auto_id)?;
-                write.write_all(&header.imei)?;
-                write.write_u8(header.session_status as u8)?;
-                write.write_u16::<BigEndian>(header.momsn)?;
-                write.write_u16::<BigEndian>(header.mtmsn)?;
-                let timestamp = header.time_of_session.timestamp();
-                if timestamp < 0 {
-                    return Err(Error::NegativeTimestamp(timestamp));
-                } else {
-                    write.write_u32::<BigEndian>(timestamp as u32)?;
-                };
-            }
-            InformationElement::Payload(ref payload) => {
-                write.write_u8(2)?;
-                let len = payload.len();
-                if len > u16::MAX as usize {
-                    return Err(Error::PayloadTooLong(len));
-                } else {
-                    write.write_u16::<BigEndian>(len as u16)?;
-                }
-                write.write_all(payload)?;
+                encode_header(header, &mut fixed)?;
+                (1, &fixed)
             }
-            InformationElement::LocationInformation(ref bytes) => {
-                write.write_u8(3)?;
-                write.write_u16::<BigEndian>(11)?;
-                write.write_all(bytes)?;
+            InformationElement::Payload(ref payload) => (2, payload),
+            InformationElement::LocationInformation(ref bytes) => (3, bytes),
+            InformationElement::Confirmation(status) => {
+                fixed[0] = u8::from(status);
+                (5, &fixed[..1])
             }
+            InformationElement::Unknown { iei, ref bytes } => (iei, bytes),
+        };
+        let length = u16::try_from(body.len()).map_err(|_| match *self {
+            InformationElement::Payload(_) => Error::PayloadTooLong(body.len()),
+            _ => Error::InformationElementTooLong(body.len()),
+        })?;
+        let needed = 3 + body.len();
+        if buffer.len() < needed {
+            return Err(Error::BufferTooSmall {
+                needed,
+                available: buffer.len(),
+            });
         }
-        Ok(())
+        buffer[0] = iei;
+        BigEndian::write_u16(&mut buffer[1..3], length);
+        buffer[3..needed].copy_from_slice(body);
+        Ok(needed)
     }
+
     /// If this is a location information element, parse it into a `MoLocation`.
     pub fn as_mo_location(&self) -> Option<Result<MoLocation, MoLocationError>> {
         match self {
@@ -138,6 +204,108 @@ impl InformationElement {
     }
 }
 
+/// Returns the number of bytes after the length field that make up this information element.
+///
+/// This is always the declared length, so the next information element is found where the sender
+/// put it. For fixed-size information elements, the declared length is checked against the
+/// specification. A declared length that's too short can't hold the fields, so it's always an
+/// error.
+pub(crate) fn body_length(iei: u8, declared: u16, options: &ParseOptions) -> Result<usize, Error> {
+    let expected = match iei {
+        1 => 28,
+        3 => 11,
+        5 => 1,
+        _ => return Ok(usize::from(declared)),
+    };
+    let err = Error::InformationElementLength {
+        iei,
+        expected,
+        actual: declared,
+    };
+    if declared < expected {
+        return Err(err);
+    } else if declared > expected {
+        options.information_element_length.apply(err)?;
+    }
+    Ok(usize::from(declared))
+}
+
+pub(crate) fn decode_header(bytes: &[u8], options: &ParseOptions) -> Result<Header, Error> {
+    let bytes: [u8; 28] = fixed(bytes)?;
+    let code = bytes[19];
+    let session_status = match SessionStatus::new(code) {
+        Ok(session_status) => session_status,
+        Err(err) => {
+            options.unknown_session_status.apply(err)?;
+            SessionStatus::Other(code)
+        }
+    };
+    let time_of_session = Utc
+        .timestamp_opt(i64::from(BigEndian::read_u32(&bytes[24..28])), 0)
+        .single()
+        .ok_or(Error::InvalidTimeOfSession)?;
+    let mut imei = [0; 15];
+    imei.copy_from_slice(&bytes[4..19]);
+    Ok(Header {
+        auto_id: BigEndian::read_u32(&bytes[0..4]),
+        imei: Imei::new(imei)?,
+        session_status,
+        momsn: BigEndian::read_u16(&bytes[20..22]),
+        mtmsn: BigEndian::read_u16(&bytes[22..24]),
+        time_of_session,
+    })
+}
+
+/// Reads exactly `length` bytes onto the end of `bytes`.
+///
+/// Memory is allocated as bytes arrive rather than all at once, so a bogus declared length can't
+/// make us allocate much more than the source actually holds.
+#[cfg(feature = "std")]
+pub(crate) fn read_onto<R: Read>(
+    mut read: R,
+    length: usize,
+    bytes: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let start = bytes.len();
+    Read::by_ref(&mut read)
+        .take(length as u64)
+        .read_to_end(bytes)?;
+    let available = bytes.len() - start;
+    if available < length {
+        return Err(Error::UnexpectedEnd {
+            needed: length,
+            available,
+        });
+    }
+    Ok(())
+}
+
+fn encode_header(header: &Header, bytes: &mut [u8; 28]) -> Result<(), Error> {
+    let timestamp = header.time_of_session.timestamp();
+    if timestamp < 0 {
+        return Err(Error::NegativeTimestamp(timestamp));
+    }
+    let timestamp = u32::try_from(timestamp).map_err(|_| Error::InvalidTimeOfSession)?;
+    BigEndian::write_u32(&mut bytes[0..4], header.auto_id);
+    bytes[4..19].copy_from_slice(header.imei.as_bytes());
+    bytes[19] = header.session_status.code();
+    BigEndian::write_u16(&mut bytes[20..22], header.momsn);
+    BigEndian::write_u16(&mut bytes[22..24], header.mtmsn);
+    BigEndian::write_u32(&mut bytes[24..28], timestamp);
+    Ok(())
+}
+
+/// Returns the first `N` bytes, ignoring any extra bytes from an overlong declared length.
+fn fixed<const N: usize>(bytes: &[u8]) -> Result<[u8; N], Error> {
+    bytes
+        .get(..N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(Error::UnexpectedEnd {
+            needed: N,
+            available: bytes.len(),
+        })
+}
+
 impl From<Header> for InformationElement {
     fn from(header: Header) -> InformationElement {
         InformationElement::Header(header)
@@ -150,6 +318,12 @@ impl From<Vec<u8>> for InformationElement {
     }
 }
 
+impl From<MoLocation> for InformationElement {
+    fn from(location: MoLocation) -> InformationElement {
+        InformationElement::LocationInformation(location.to_bytes())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -158,8 +332,10 @@ mod tests {
     };
 
     use chrono::TimeZone;
+    use proptest::prelude::*;
 
     use super::*;
+    use crate::mo::strategy;
 
     #[test]
     fn read_from() {
@@ -170,7 +346,7 @@ mod tests {
             match InformationElement::read_from(read).unwrap() {
                 InformationElement::Header(header) => {
                     assert_eq!(1894516585, header.auto_id);
-                    assert_eq!(b"300234063904190", &header.imei);
+                    assert_eq!("300234063904190", header.imei);
                     assert_eq!(SessionStatus::Ok, header.session_status);
                     assert_eq!(75, header.momsn);
                     assert_eq!(0, header.mtmsn);
@@ -204,7 +380,7 @@ mod tests {
     fn header_len() {
         let header = Header {
             auto_id: 1,
-            imei: [0; 15],
+            imei: "000000000000000".parse().unwrap(),
             session_status: SessionStatus::Ok,
             momsn: 1,
             mtmsn: 1,
@@ -231,7 +407,7 @@ mod tests {
     fn roundtrip_header() {
         let header = Header {
             auto_id: 1,
-            imei: [0; 15],
+            imei: "000000000000000".parse().unwrap(),
             session_status: SessionStatus::Ok,
             momsn: 1,
             mtmsn: 1,
@@ -251,7 +427,7 @@ mod tests {
     fn header_time_of_session_too_old() {
         let header = Header {
             auto_id: 1,
-            imei: [0; 15],
+            imei: "000000000000000".parse().unwrap(),
             session_status: SessionStatus::Ok,
             momsn: 1,
             mtmsn: 1,
@@ -291,4 +467,82 @@ mod tests {
         cursor.set_position(0);
         assert_eq!(ie, InformationElement::read_from(cursor).unwrap());
     }
+
+    #[test]
+    fn confirmation_len() {
+        assert_eq!(4, InformationElement::Confirmation(true).len());
+    }
+
+    #[test]
+    fn read_confirmation() {
+        let bytes = [0x05, 0x00, 0x01, 0x01];
+        assert_eq!(
+            InformationElement::Confirmation(true),
+            InformationElement::read_from(&bytes[..]).unwrap()
+        );
+        let bytes = [0x05, 0x00, 0x01, 0x00];
+        assert_eq!(
+            InformationElement::Confirmation(false),
+            InformationElement::read_from(&bytes[..]).unwrap()
+        );
+        let bytes = [0x05, 0x00, 0x01, 0x02];
+        assert!(InformationElement::read_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn roundtrip_confirmation() {
+        for status in [true, false] {
+            let ie = InformationElement::Confirmation(status);
+            let mut cursor = Cursor::new(Vec::new());
+            ie.write_to(&mut cursor).unwrap();
+            assert_eq!(ie.len(), cursor.get_ref().len());
+            cursor.set_position(0);
+            assert_eq!(ie, InformationElement::read_from(cursor).unwrap());
+        }
+    }
+
+    #[test]
+    fn read_unknown() {
+        let bytes = [0x06, 0x00, 0x02, 0xAB, 0xCD, 0xFF];
+        assert_eq!(
+            InformationElement::Unknown {
+                iei: 6,
+                bytes: vec![0xAB, 0xCD]
+            },
+            InformationElement::read_from(&bytes[..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn roundtrip_unknown() {
+        let ie = InformationElement::Unknown {
+            iei: 0x20,
+            bytes: vec![1, 2, 3],
+        };
+        assert_eq!(6, ie.len());
+        let mut cursor = Cursor::new(Vec::new());
+        ie.write_to(&mut cursor).unwrap();
+        assert_eq!(&[0x20, 0x00, 0x03, 1, 2, 3], cursor.get_ref().as_slice());
+        cursor.set_position(0);
+        assert_eq!(ie, InformationElement::read_from(cursor).unwrap());
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip(information_element in strategy::information_element()) {
+            let mut bytes = Vec::new();
+            information_element.write_to(&mut bytes).unwrap();
+            prop_assert_eq!(information_element.len(), bytes.len());
+            prop_assert_eq!(
+                information_element,
+                InformationElement::read_from(&bytes[..]).unwrap()
+            );
+        }
+
+        #[test]
+        fn read_from_arbitrary_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let _ = InformationElement::read_from(&bytes[..]);
+            let _ = InformationElement::read_from_with(&bytes[..], &ParseOptions::lenient());
+        }
+    }
 }
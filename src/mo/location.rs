@@ -1,4 +1,19 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use thiserror::Error;
+
+/// The location of the IMEI, as reported by the Iridium gateway.
+///
+/// Latitude and longitude are stored as degrees and thousandths of a minute, just like on the
+/// wire, so a location survives `MoLocation::parse` and `MoLocation::to_bytes` unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mo::MoLocation;
+/// let location = MoLocation::new(-43.52117, 172.60487, 2).unwrap();
+/// assert_eq!(location, MoLocation::parse(location.to_bytes()).unwrap());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MoLocation {
     /// 0 = North, 1 = South (per spec)
     pub north: bool,
@@ -19,16 +34,74 @@ pub struct MoLocation {
     pub cep_km: u32,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Errors that can occur when parsing a `MoLocation`.
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MoLocationError {
+    /// The reserved bits of the first byte are not zero.
+    #[error("reserved location bits are not zero: {0:#010b}")]
     ReservedBitsNonZero(u8),
+    /// The latitude degrees are greater than 90.
+    #[error("latitude degrees out of range: {0}")]
     LatDegOutOfRange(u8),
+    /// The latitude thousandths of a minute are greater than 59,999.
+    #[error("latitude thousandths of a minute out of range: {0}")]
     LatThousandthsOutOfRange(u16),
+    /// The longitude degrees are greater than 180.
+    #[error("longitude degrees out of range: {0}")]
     LonDegOutOfRange(u8),
+    /// The longitude thousandths of a minute are greater than 59,999.
+    #[error("longitude thousandths of a minute out of range: {0}")]
     LonThousandthsOutOfRange(u16),
+    /// The latitude, in decimal degrees, is not finite or not in -90..=90.
+    #[error("latitude is not between -90 and 90 degrees")]
+    LatitudeOutOfRange,
+    /// The longitude, in decimal degrees, is not finite or not in -180..=180.
+    #[error("longitude is not between -180 and 180 degrees")]
+    LongitudeOutOfRange,
 }
 
 impl MoLocation {
+    /// Creates a location from decimal degrees and a CEP radius in kilometers.
+    ///
+    /// Latitude is positive north and longitude is positive east, and the sign is kept even for
+    /// zero (e.g. `-0.0` is south or west). Both are rounded to the
+    /// nearest thousandth of a minute, the resolution of the location information element.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the latitude isn't in -90..=90 or the longitude isn't in -180..=180.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MoLocation;
+    /// let location = MoLocation::new(37.5, -122.25, 5).unwrap();
+    /// assert_eq!(37, location.lat_deg);
+    /// assert_eq!(30_000, location.lat_thousandths_min);
+    /// assert!(!location.east);
+    /// assert!(MoLocation::new(91.0, 0.0, 5).is_err());
+    /// ```
+    pub fn new(latitude: f64, longitude: f64, cep_km: u32) -> Result<MoLocation, MoLocationError> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(MoLocationError::LatitudeOutOfRange);
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(MoLocationError::LongitudeOutOfRange);
+        }
+        let (lat_deg, lat_thousandths_min) = to_degrees_and_thousandths(latitude);
+        let (lon_deg, lon_thousandths_min) = to_degrees_and_thousandths(longitude);
+        Ok(MoLocation {
+            north: latitude.is_sign_positive(),
+            east: longitude.is_sign_positive(),
+            lat_deg,
+            lat_thousandths_min,
+            lon_deg,
+            lon_thousandths_min,
+            cep_km,
+        })
+    }
+
     /// Parse the 11-byte MO Location IE value into fields.
     ///
     /// Layout (11 bytes total):
@@ -94,6 +167,34 @@ impl MoLocation {
         })
     }
 
+    /// Encodes this location into the 11-byte MO Location IE value.
+    ///
+    /// This is the inverse of `MoLocation::parse`. The fields aren't checked, so an out-of-range
+    /// location will fail to parse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MoLocation;
+    /// let bytes = [0x01, 0x25, 0x75, 0x30, 0x7A, 0x3A, 0x98, 0x00, 0x00, 0x00, 0x05];
+    /// assert_eq!(bytes, MoLocation::parse(bytes).unwrap().to_bytes());
+    /// ```
+    pub fn to_bytes(&self) -> [u8; 11] {
+        let mut bytes = [0; 11];
+        if !self.north {
+            bytes[0] |= 0b0000_0010;
+        }
+        if !self.east {
+            bytes[0] |= 0b0000_0001;
+        }
+        bytes[1] = self.lat_deg;
+        bytes[2..4].copy_from_slice(&self.lat_thousandths_min.to_be_bytes());
+        bytes[4] = self.lon_deg;
+        bytes[5..7].copy_from_slice(&self.lon_thousandths_min.to_be_bytes());
+        bytes[7..11].copy_from_slice(&self.cep_km.to_be_bytes());
+        bytes
+    }
+
     /// Decimal degrees, positive north, negative south.
     pub fn latitude_deg(&self) -> f64 {
         let minutes = self.lat_thousandths_min as f64 / 1000.0;
@@ -117,9 +218,23 @@ impl MoLocation {
     }
 }
 
+/// Splits an absolute value in decimal degrees into whole degrees and thousandths of a minute.
+///
+/// The caller has checked that the value is in range, so the degrees fit in a `u8`.
+fn to_degrees_and_thousandths(value: f64) -> (u8, u16) {
+    // Round half away from zero by hand, since `f64::round` needs `std`.
+    let magnitude = if value < 0.0 { -value } else { value };
+    let thousandths = (magnitude * 60_000.0 + 0.5) as u32;
+    ((thousandths / 60_000) as u8, (thousandths % 60_000) as u16)
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
+    use crate::mo::strategy;
+
     #[test]
     fn test_parse_mo_location() {
         // flags=01 (north, west), 37° 30.000' N, 122° 15.000' W, CEP=5 km
@@ -141,4 +256,87 @@ mod tests {
         assert!((lat - 37.5).abs() < 1e-9);
         assert!((lon + 122.25).abs() < 1e-9);
     }
+
+    #[test]
+    fn new() {
+        let location = MoLocation::new(-43.52117, 172.60487, 2).unwrap();
+        let bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        assert_eq!(&bytes[37..48], &location.to_bytes());
+
+        assert!(MoLocation::new(90.0, 180.0, 0).is_ok());
+        assert!(MoLocation::new(-90.0, -180.0, 0).is_ok());
+        assert_eq!(
+            Err(MoLocationError::LatitudeOutOfRange),
+            MoLocation::new(90.001, 0.0, 0)
+        );
+        assert_eq!(
+            Err(MoLocationError::LongitudeOutOfRange),
+            MoLocation::new(0.0, -180.001, 0)
+        );
+        assert!(MoLocation::new(f64::NAN, 0.0, 0).is_err());
+        assert!(MoLocation::new(0.0, f64::INFINITY, 0).is_err());
+    }
+
+    #[test]
+    fn rounding_carries_into_degrees() {
+        // 10.9999999 degrees is 10 degrees and 59.999994 minutes, which rounds up.
+        let location = MoLocation::new(10.999_999_9, -0.000_000_1, 0).unwrap();
+        assert_eq!(11, location.lat_deg);
+        assert_eq!(0, location.lat_thousandths_min);
+        assert_eq!(0, location.lon_deg);
+        assert_eq!(0, location.lon_thousandths_min);
+        assert!(!location.east);
+        assert!(MoLocation::parse(location.to_bytes()).is_ok());
+    }
+
+    #[test]
+    fn roundtrip() {
+        for flags in 0..4 {
+            for (lat, lon) in [(0, 0), (45, 90), (89, 179)] {
+                for thousandths in [0, 1, 30_000, 59_999] {
+                    let bytes = [
+                        flags,
+                        lat,
+                        (thousandths >> 8) as u8,
+                        thousandths as u8,
+                        lon,
+                        (thousandths >> 8) as u8,
+                        thousandths as u8,
+                        0,
+                        0,
+                        1,
+                        2,
+                    ];
+                    let location = MoLocation::parse(bytes).unwrap();
+                    assert_eq!(bytes, location.to_bytes());
+                    let new =
+                        MoLocation::new(location.latitude_deg(), location.longitude_deg(), 258);
+                    assert_eq!(location, new.unwrap());
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip_location(location in strategy::location()) {
+            prop_assert_eq!(location, MoLocation::parse(location.to_bytes()).unwrap());
+        }
+
+        #[test]
+        fn roundtrip_bytes(bytes in any::<[u8; 11]>()) {
+            if let Ok(location) = MoLocation::parse(bytes) {
+                prop_assert_eq!(bytes, location.to_bytes());
+            }
+        }
+
+        #[test]
+        fn roundtrip_degrees(latitude in -90.0..=90.0f64, longitude in -180.0..=180.0f64) {
+            let location = MoLocation::new(latitude, longitude, 0).unwrap();
+            prop_assert_eq!(location, MoLocation::parse(location.to_bytes()).unwrap());
+            // Half a thousandth of a minute, plus some slack for floating point.
+            prop_assert!((location.latitude_deg() - latitude).abs() < 1e-8 + 0.5 / 60_000.0);
+            prop_assert!((location.longitude_deg() - longitude).abs() < 1e-8 + 0.5 / 60_000.0);
+        }
+    }
 }
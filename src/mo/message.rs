@@ -1,23 +1,50 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+#[cfg(feature = "std")]
 use std::{
-    cmp::Ordering,
-    io::{Cursor, Read, Write},
+    io::{self, Read, Write},
     path::Path,
 };
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+use byteorder::ReadBytesExt;
+use byteorder::{BigEndian, ByteOrder};
 use chrono::{DateTime, Utc};
 
+#[cfg(feature = "std")]
+use crate::mo::{information_element::read_onto, Policy};
 use crate::{
-    mo::{Header, InformationElement, SessionStatus},
-    Error,
+    mo::{
+        Header, InformationElement, MessageBuilder, MessageRef, MoLocation, MoLocationError,
+        ParseOptions, SessionStatus,
+    },
+    Error, Imei,
 };
 
 /// The only valid protocol revision number.
 pub const PROTOCOL_REVISION_NUMBER: u8 = 1;
 
+/// The most trailing bytes that are read when checking for them, about one more message.
+#[cfg(feature = "std")]
+pub(crate) const MAX_TRAILING_BYTES: u64 = 3 + u16::MAX as u64;
+
 /// A mobile-origined Iridium SBD message.
 ///
 /// `Message`s can be ordered by time of session.
+///
+/// With the `serde` feature, a `Message` serializes to an object with `header`, `payload`, and
+/// `information_elements` fields. The payload is an object holding its `encoding` and the
+/// encoded `data`, base64 by default; use `Message::with_payload_encoding` to choose another
+/// encoding. Deserializing that object gives back a message that writes the same bytes.
+///
+/// ```
+/// # #[cfg(feature = "serde")] {
+/// use sbd::mo::Message;
+/// let message = Message::from_path("data/0-mo.sbd").unwrap();
+/// let json = serde_json::to_string(&message).unwrap();
+/// assert_eq!(message, serde_json::from_str(&json).unwrap());
+/// # }
+/// ```
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Message {
     header: Header,
@@ -34,6 +61,7 @@ impl Message {
     /// use sbd::mo::Message;
     /// let message = Message::from_path("data/0-mo.sbd").unwrap();
     /// ```
+    #[cfg(feature = "std")]
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Message, Error> {
         use std::fs::File;
         let file = File::open(path)?;
@@ -42,7 +70,12 @@ impl Message {
 
     /// Reads in a message from an object that implements `Read`.
     ///
-    /// Per the specification, oversized and undersized messages will result in an error.
+    /// Per the specification, oversized and undersized messages will result in an error. Errors
+    /// in information elements are reported as `Error::InformationElement`, which includes the
+    /// byte offset of the offending information element.
+    ///
+    /// This is safe to use on untrusted input: it never panics, and it allocates memory as bytes
+    /// arrive, so a message can't claim to be longer than it is to exhaust memory.
     ///
     /// # Examples
     ///
@@ -52,7 +85,24 @@ impl Message {
     /// let mut file = File::open("data/0-mo.sbd").unwrap();
     /// let message = Message::read_from(file).unwrap();
     /// ```
-    pub fn read_from<R: Read>(mut read: R) -> Result<Message, Error> {
+    #[cfg(feature = "std")]
+    pub fn read_from<R: Read>(read: R) -> Result<Message, Error> {
+        Message::read_from_with(read, &ParseOptions::default())
+    }
+
+    /// Reads in a message from an object that implements `Read`, using the provided
+    /// `ParseOptions`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use sbd::mo::{Message, ParseOptions};
+    /// let file = File::open("data/0-mo.sbd").unwrap();
+    /// let message = Message::read_from_with(file, &ParseOptions::lenient()).unwrap();
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn read_from_with<R: Read>(mut read: R, options: &ParseOptions) -> Result<Message, Error> {
         let protocol_revision_number = read.read_u8()?;
         if protocol_revision_number != PROTOCOL_REVISION_NUMBER {
             return Err(Error::InvalidProtocolRevisionNumber(
@@ -60,16 +110,52 @@ impl Message {
             ));
         }
         let overall_message_length = read.read_u16::<BigEndian>()?;
-        let mut message = vec![0; overall_message_length as usize];
-        read.read_exact(&mut message)?;
-
-        let mut cursor = Cursor::new(message);
-        let mut information_elements = Vec::new();
-        while cursor.position() < u64::from(overall_message_length) {
-            information_elements.push(InformationElement::read_from(&mut cursor)?);
+        let mut bytes = vec![protocol_revision_number, 0, 0];
+        BigEndian::write_u16(&mut bytes[1..3], overall_message_length);
+        read_onto(&mut read, usize::from(overall_message_length), &mut bytes)?;
+        let message = MessageRef::parse_with(&bytes, options)?.to_message();
+
+        if options.trailing_bytes != Policy::Ignore {
+            let trailing_bytes = io::copy(&mut read.take(MAX_TRAILING_BYTES), &mut io::sink())?;
+            if trailing_bytes > 0 {
+                options
+                    .trailing_bytes
+                    .apply(Error::TrailingBytes(trailing_bytes as usize))?;
+            }
         }
 
-        Message::new(information_elements)
+        Ok(message)
+    }
+
+    /// Decodes a message from the start of a byte slice, using the default `ParseOptions`.
+    ///
+    /// This is the slice-based counterpart to `Message::read_from`, and is available without the
+    /// `std` feature. Any bytes after the end of the message are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let message = Message::decode(&bytes).unwrap();
+    /// assert_eq!(75, message.momsn());
+    /// ```
+    pub fn decode(bytes: &[u8]) -> Result<Message, Error> {
+        Message::decode_with(bytes, &ParseOptions::default())
+    }
+
+    /// Decodes a message from the start of a byte slice, using the provided `ParseOptions`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{Message, ParseOptions};
+    /// let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// bytes.push(0);
+    /// assert!(Message::decode_with(&bytes, &ParseOptions::strict()).is_err());
+    /// ```
+    pub fn decode_with(bytes: &[u8], options: &ParseOptions) -> Result<Message, Error> {
+        MessageRef::parse_with(bytes, options).map(|message| message.to_message())
     }
 
     /// Creates a new message from information elements.
@@ -84,7 +170,7 @@ impl Message {
     /// use sbd::mo::{InformationElement, Header, SessionStatus, Message};
     /// let header = InformationElement::Header(Header {
     ///     auto_id: 1,
-    ///     imei: [0; 15],
+    ///     imei: "300234063904190".parse().unwrap(),
     ///     session_status: SessionStatus::Ok,
     ///     momsn: 1,
     ///     mtmsn: 0,
@@ -95,21 +181,61 @@ impl Message {
     /// # }
     /// ```
     pub fn new<I: IntoIterator<Item = InformationElement>>(iter: I) -> Result<Message, Error> {
+        Message::from_information_elements(iter, &ParseOptions::default())
+    }
+
+    /// Returns a builder for a new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use sbd::mo::Message;
+    /// let message = Message::builder()
+    ///     .imei("300234063904190")
+    ///     .time_of_session(Utc::now())
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> MessageBuilder {
+        MessageBuilder::new()
+    }
+
+    pub(crate) fn from_parts(
+        header: Header,
+        payload: Vec<u8>,
+        information_elements: Vec<InformationElement>,
+    ) -> Message {
+        Message {
+            header,
+            payload,
+            information_elements,
+        }
+    }
+
+    fn from_information_elements<I: IntoIterator<Item = InformationElement>>(
+        iter: I,
+        options: &ParseOptions,
+    ) -> Result<Message, Error> {
         let mut header = None;
-        let mut payload = None;
+        let mut payload: Option<Vec<u8>> = None;
         let mut information_elements = Vec::new();
         for information_element in iter {
             match information_element {
                 InformationElement::Header(h) => {
                     if let Some(header) = header {
-                        return Err(Error::TwoHeaders(h, header));
+                        options
+                            .duplicate_information_elements
+                            .apply(Error::TwoHeaders(h, header))?;
                     } else {
                         header = Some(h);
                     }
                 }
                 InformationElement::Payload(p) => {
-                    if let Some(payload) = payload {
-                        return Err(Error::TwoPayloads(p, payload));
+                    if let Some(ref payload) = payload {
+                        options
+                            .duplicate_information_elements
+                            .apply(Error::TwoPayloads(p, payload.clone()))?;
                     } else {
                         payload = Some(p);
                     }
@@ -124,6 +250,19 @@ impl Message {
         })
     }
 
+    /// Returns this message's header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// let message = Message::from_path("data/0-mo.sbd").unwrap();
+    /// assert_eq!(75, message.header().momsn);
+    /// ```
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
     /// Returns this message's auto id.
     ///
     /// # Examples
@@ -137,12 +276,7 @@ impl Message {
         self.header.auto_id
     }
 
-    /// Returns this message's imei as a string.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the IMEI number is not valid utf8. The specification says that IMEIs should be
-    /// ascii numbers.
+    /// Returns this message's imei.
     ///
     /// # Examples
     ///
@@ -151,9 +285,8 @@ impl Message {
     /// let message = Message::from_path("data/0-mo.sbd").unwrap();
     /// let imei = message.imei();
     /// ```
-    pub fn imei(&self) -> &str {
-        use std::str;
-        str::from_utf8(&self.header.imei).expect("IMEI numbers are specified to be ascii number")
+    pub fn imei(&self) -> Imei {
+        self.header.imei
     }
 
     /// Returns this message's session status.
@@ -233,6 +366,23 @@ impl Message {
         &self.information_elements
     }
 
+    /// Returns this message's location, if it has a location information element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// let message = Message::from_path("data/2-location.mo.sbd").unwrap();
+    /// let location = message.location().unwrap().unwrap();
+    /// assert_eq!(2, location.cep_km);
+    /// assert!(Message::from_path("data/0-mo.sbd").unwrap().location().is_none());
+    /// ```
+    pub fn location(&self) -> Option<Result<MoLocation, MoLocationError>> {
+        self.information_elements
+            .iter()
+            .find_map(InformationElement::as_mo_location)
+    }
+
     /// Write this message back to a object that can `Write`.
     ///
     /// # Examples
@@ -244,31 +394,91 @@ impl Message {
     /// let mut cursor = Cursor::new(Vec::new());
     /// message.write_to(&mut cursor);
     /// ```
+    #[cfg(feature = "std")]
     pub fn write_to<W: Write>(&self, mut write: W) -> Result<(), Error> {
-        let header = InformationElement::from(self.header);
-        let payload = InformationElement::from(self.payload.clone());
-        let overall_message_length = header.len()
-            + payload.len()
-            + self
-                .information_elements
-                .iter()
-                .map(|ie| ie.len())
-                .sum::<usize>();
-        if overall_message_length > u16::MAX as usize {
+        let mut bytes = vec![0; self.encoded_len()];
+        let _ = self.encode_into(&mut bytes)?;
+        write.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Returns the number of bytes needed to encode this message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// let message = Message::from_path("data/0-mo.sbd").unwrap();
+    /// assert_eq!(59, message.encoded_len());
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        3 + self.overall_message_length()
+    }
+
+    /// Encodes this message into the start of a byte slice.
+    ///
+    /// Returns the number of bytes written. This is the slice-based counterpart to
+    /// `Message::write_to`, and is available without the `std` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message can't be encoded (e.g. it is too long) or if the buffer is
+    /// smaller than `encoded_len()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::Message;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let message = Message::decode(&bytes).unwrap();
+    /// let mut buffer = [0; 1024];
+    /// let n = message.encode_into(&mut buffer).unwrap();
+    /// assert_eq!(bytes, &buffer[..n]);
+    /// assert!(message.encode_into(&mut buffer[..10]).is_err());
+    /// ```
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let overall_message_length = self.overall_message_length();
+        if overall_message_length > usize::from(u16::MAX) {
             return Err(Error::OverallMessageLength(overall_message_length));
         }
-
-        write.write_u8(PROTOCOL_REVISION_NUMBER)?;
-        write.write_u16::<BigEndian>(overall_message_length as u16)?;
-        header.write_to(&mut write)?;
-        payload.write_to(&mut write)?;
+        let needed = 3 + overall_message_length;
+        if buffer.len() < needed {
+            return Err(Error::BufferTooSmall {
+                needed,
+                available: buffer.len(),
+            });
+        }
+        buffer[0] = PROTOCOL_REVISION_NUMBER;
+        BigEndian::write_u16(&mut buffer[1..3], overall_message_length as u16);
+        let mut offset = 3;
+        offset += InformationElement::from(self.header).encode_into(&mut buffer[offset..])?;
+        offset += encode_payload(&self.payload, &mut buffer[offset..])?;
         for information_element in &self.information_elements {
-            information_element.write_to(&mut write)?;
+            offset += information_element.encode_into(&mut buffer[offset..])?;
         }
-        Ok(())
+        Ok(offset)
+    }
+
+    fn overall_message_length(&self) -> usize {
+        31 + 3
+            + self.payload.len()
+            + self
+                .information_elements
+                .iter()
+                .map(|ie| ie.len())
+                .sum::<usize>()
     }
 }
 
+/// Encodes the payload information element without copying the payload.
+fn encode_payload(payload: &[u8], buffer: &mut [u8]) -> Result<usize, Error> {
+    let length = u16::try_from(payload.len()).map_err(|_| Error::PayloadTooLong(payload.len()))?;
+    buffer[0] = 2;
+    BigEndian::write_u16(&mut buffer[1..3], length);
+    buffer[3..3 + payload.len()].copy_from_slice(payload);
+    Ok(3 + payload.len())
+}
+
 impl PartialOrd for Message {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -290,14 +500,15 @@ mod tests {
     };
 
     use chrono::{TimeZone, Utc};
+    use proptest::{collection::vec, prelude::*};
 
     use super::*;
-    use crate::mo::Header;
+    use crate::mo::{strategy, Header};
 
     pub fn header() -> Header {
         Header {
             auto_id: 1,
-            imei: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14],
+            imei: "300234063904190".parse().unwrap(),
             session_status: SessionStatus::Ok,
             momsn: 1,
             mtmsn: 0,
@@ -392,6 +603,158 @@ mod tests {
         assert_eq!(message, message2);
     }
 
+    #[test]
+    fn unknown_information_element() {
+        let message = Message::new(vec![
+            header().into(),
+            vec![1].into(),
+            InformationElement::Unknown {
+                iei: 0x7f,
+                bytes: vec![1, 2, 3, 4],
+            },
+        ])
+        .unwrap();
+        let mut cursor = Cursor::new(Vec::new());
+        message.write_to(&mut cursor).unwrap();
+        let bytes = cursor.into_inner();
+        let message2 = Message::read_from(&bytes[..]).unwrap();
+        assert_eq!(message, message2);
+        let mut bytes2 = Vec::new();
+        message2.write_to(&mut bytes2).unwrap();
+        assert_eq!(bytes, bytes2);
+    }
+
+    #[test]
+    fn two_headers_lenient() {
+        let header = header();
+        let mut header2 = header;
+        header2.momsn = 2;
+        let mut bytes = vec![1, 0, 66];
+        InformationElement::from(header)
+            .write_to(&mut bytes)
+            .unwrap();
+        InformationElement::from(header2)
+            .write_to(&mut bytes)
+            .unwrap();
+        InformationElement::from(vec![1])
+            .write_to(&mut bytes)
+            .unwrap();
+        assert!(Message::read_from(&bytes[..]).is_err());
+        let message = Message::read_from_with(&bytes[..], &ParseOptions::lenient()).unwrap();
+        assert_eq!(1, message.momsn());
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        bytes.push(0);
+        assert!(Message::read_from(&bytes[..]).is_ok());
+        assert!(Message::read_from_with(&bytes[..], &ParseOptions::strict()).is_err());
+        assert!(Message::read_from_with(&bytes[..], &ParseOptions::lenient()).is_ok());
+    }
+
+    #[test]
+    fn endless_trailing_bytes() {
+        let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        let read = (&bytes[..]).chain(io::repeat(0));
+        match Message::read_from_with(read, &ParseOptions::strict()) {
+            Err(Error::TrailingBytes(n)) => assert_eq!(MAX_TRAILING_BYTES as usize, n),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_session_status() {
+        let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        bytes[25] = 3;
+        assert!(Message::read_from_with(&bytes[..], &ParseOptions::strict()).is_err());
+        let message = Message::read_from(&bytes[..]).unwrap();
+        assert_eq!(SessionStatus::Other(3), message.session_status());
+        let mut bytes2 = Vec::new();
+        message.write_to(&mut bytes2).unwrap();
+        assert_eq!(bytes, bytes2);
+    }
+
+    #[test]
+    fn invalid_location() {
+        let message = Message::new(vec![
+            header().into(),
+            vec![1].into(),
+            InformationElement::LocationInformation([0xff; 11]),
+        ])
+        .unwrap();
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        assert!(Message::read_from(&bytes[..]).is_ok());
+        assert!(Message::read_from_with(&bytes[..], &ParseOptions::strict()).is_err());
+        assert_eq!(
+            message,
+            Message::read_from_with(&bytes[..], &ParseOptions::lenient()).unwrap()
+        );
+    }
+
+    #[test]
+    fn information_element_length() {
+        let mut bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        bytes[36] = 12;
+        match Message::read_from(&bytes[..]).unwrap_err() {
+            Error::InformationElement {
+                iei,
+                offset,
+                source,
+            } => {
+                assert_eq!(3, iei);
+                assert_eq!(34, offset);
+                match *source {
+                    Error::InformationElementLength {
+                        iei,
+                        expected,
+                        actual,
+                    } => {
+                        assert_eq!(3, iei);
+                        assert_eq!(11, expected);
+                        assert_eq!(12, actual);
+                    }
+                    err => panic!("unexpected error: {}", err),
+                }
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn overlong_information_elements() {
+        let location = InformationElement::LocationInformation([0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 1]);
+        let mut bytes = vec![PROTOCOL_REVISION_NUMBER, 0, 0];
+        for information_element in [header().into(), location.clone()] {
+            let start = bytes.len();
+            information_element.write_to(&mut bytes).unwrap();
+            bytes[start + 2] += 1;
+            bytes.push(0);
+        }
+        InformationElement::from(b"hello".to_vec())
+            .write_to(&mut bytes)
+            .unwrap();
+        let length = bytes.len() as u16 - 3;
+        BigEndian::write_u16(&mut bytes[1..3], length);
+
+        assert!(Message::read_from_with(&bytes[..], &ParseOptions::strict()).is_err());
+        let message = Message::read_from_with(&bytes[..], &ParseOptions::lenient()).unwrap();
+        assert_eq!(&header(), message.header());
+        assert_eq!(b"hello", message.payload().as_slice());
+        assert_eq!(&vec![location], message.information_elements());
+
+        bytes[5] = 27;
+        assert!(Message::read_from_with(&bytes[..], &ParseOptions::lenient()).is_err());
+    }
+
+    #[test]
+    fn invalid_imei() {
+        let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        bytes[10] = 0xff;
+        assert!(Message::read_from(&bytes[..]).is_err());
+    }
+
     #[test]
     fn order() {
         let header1 = header();
@@ -401,4 +764,73 @@ mod tests {
         let message2 = Message::new(vec![header2.into(), Vec::new().into()]).unwrap();
         assert!(message2 < message1);
     }
+
+    proptest! {
+        #[test]
+        fn roundtrip(message in strategy::message()) {
+            let mut bytes = Vec::new();
+            message.write_to(&mut bytes).unwrap();
+            prop_assert_eq!(&message, &Message::read_from(&bytes[..]).unwrap());
+            prop_assert_eq!(&message, &Message::decode(&bytes).unwrap());
+            let mut buffer = vec![0; message.encoded_len() + 1];
+            prop_assert_eq!(bytes.len(), message.encode_into(&mut buffer).unwrap());
+            prop_assert_eq!(&bytes[..], &buffer[..bytes.len()]);
+            prop_assert_eq!(message, MessageRef::parse(&bytes).unwrap().to_message());
+        }
+
+        #[test]
+        fn read_from_arbitrary_bytes(bytes in vec(any::<u8>(), 0..256)) {
+            let _ = Message::read_from(&bytes[..]);
+            let _ = Message::read_from_with(&bytes[..], &ParseOptions::lenient());
+        }
+
+        #[test]
+        fn read_from_corrupted_message(
+            message in strategy::message(),
+            corruptions in vec((any::<prop::sample::Index>(), any::<u8>()), 1..4),
+        ) {
+            let mut bytes = Vec::new();
+            message.write_to(&mut bytes).unwrap();
+            for (index, value) in corruptions {
+                let index = index.index(bytes.len());
+                bytes[index] = value;
+            }
+            let result = Message::read_from(&bytes[..]);
+            prop_assert_eq!(result.is_err(), Message::explain(&bytes).has_errors());
+            let _ = Message::read_from_with(&bytes[..], &ParseOptions::lenient());
+            if let Ok(message) = result {
+                let mut bytes = Vec::new();
+                message.write_to(&mut bytes).unwrap();
+                prop_assert_eq!(message, Message::read_from(&bytes[..]).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn encode_into_small_buffer() {
+        let message = Message::from_path("data/2-location.mo.sbd").unwrap();
+        let mut buffer = vec![0; message.encoded_len()];
+        for length in 0..buffer.len() {
+            match message.encode_into(&mut buffer[..length]).unwrap_err() {
+                Error::BufferTooSmall { needed, available } => {
+                    assert_eq!(message.encoded_len(), needed);
+                    assert_eq!(length, available);
+                }
+                err => panic!("unexpected error: {}", err),
+            }
+        }
+        assert!(message.encode_into(&mut buffer).is_ok());
+    }
+
+    #[test]
+    fn huge_declared_length() {
+        let bytes = [1, 0xff, 0xff, 1, 0xff, 0xff];
+        match Message::read_from(&bytes[..]).unwrap_err() {
+            Error::UnexpectedEnd { needed, available } => {
+                assert_eq!(0xffff, needed);
+                assert_eq!(3, available);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
 }
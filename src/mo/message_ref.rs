@@ -0,0 +1,481 @@
+//! Borrowed, zero-copy views of mobile-originated messages.
+
+use alloc::boxed::Box;
+
+use byteorder::{BigEndian, ByteOrder};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    mo::{
+        information_element::{body_length, decode_header},
+        Header, InformationElement, Message, ParseOptions, Policy, SessionStatus,
+        PROTOCOL_REVISION_NUMBER,
+    },
+    Error, Imei,
+};
+
+/// A validated mobile-originated message that borrows its contents from a byte slice.
+///
+/// Parsing a `MessageRef` checks the message just as `Message::read_from` does, but it doesn't
+/// copy the payload or any other information element. Use `MessageRef::to_message` to get an
+/// owned `Message`.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mo::MessageRef;
+/// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+/// let message = MessageRef::parse(&bytes).unwrap();
+/// assert_eq!(b"test message from pete", message.payload());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct MessageRef<'a> {
+    bytes: &'a [u8],
+    header: Header,
+    payload: &'a [u8],
+    options: ParseOptions,
+}
+
+/// A borrowed information element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InformationElementRef<'a> {
+    iei: u8,
+    offset: usize,
+    bytes: &'a [u8],
+}
+
+/// An iterator over the information elements of a `MessageRef`.
+#[derive(Clone, Debug)]
+pub struct InformationElementRefs<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    options: ParseOptions,
+}
+
+impl<'a> MessageRef<'a> {
+    /// Parses a message from the start of a byte slice, using the default `ParseOptions`.
+    ///
+    /// Any bytes after the end of the message are ignored. Use `MessageRef::as_bytes` to find out
+    /// how many bytes the message used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let message = MessageRef::parse(&bytes).unwrap();
+    /// assert!(MessageRef::parse(&bytes[..10]).is_err());
+    /// ```
+    pub fn parse(bytes: &'a [u8]) -> Result<MessageRef<'a>, Error> {
+        MessageRef::parse_with(bytes, &ParseOptions::default())
+    }
+
+    /// Parses a message from the start of a byte slice, using the provided `ParseOptions`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{MessageRef, ParseOptions};
+    /// let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// bytes.push(42);
+    /// assert!(MessageRef::parse_with(&bytes, &ParseOptions::strict()).is_err());
+    /// ```
+    pub fn parse_with(bytes: &'a [u8], options: &ParseOptions) -> Result<MessageRef<'a>, Error> {
+        if bytes.len() < 3 {
+            return Err(Error::UnexpectedEnd {
+                needed: 3,
+                available: bytes.len(),
+            });
+        }
+        if bytes[0] != PROTOCOL_REVISION_NUMBER {
+            return Err(Error::InvalidProtocolRevisionNumber(bytes[0]));
+        }
+        let length = 3 + usize::from(BigEndian::read_u16(&bytes[1..3]));
+        if bytes.len() < length {
+            return Err(Error::UnexpectedEnd {
+                needed: length,
+                available: bytes.len(),
+            });
+        }
+        if bytes.len() > length {
+            options
+                .trailing_bytes
+                .apply(Error::TrailingBytes(bytes.len() - length))?;
+        }
+        let bytes = &bytes[..length];
+
+        let mut header: Option<Header> = None;
+        let mut payload: Option<&[u8]> = None;
+        let mut offset = 3;
+        while offset < length {
+            let information_element = InformationElementRef::next(bytes, offset, options)?;
+            offset += information_element.len();
+            information_element
+                .validate(&mut header, &mut payload, options)
+                .map_err(|err| information_element.error(err))?;
+        }
+        Ok(MessageRef {
+            bytes,
+            header: header.ok_or(Error::NoHeader)?,
+            payload: payload.ok_or(Error::NoPayload)?,
+            options: *options,
+        })
+    }
+
+    /// Returns the bytes of this message, from the protocol revision number through the last
+    /// information element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let message = MessageRef::parse(&bytes).unwrap();
+    /// assert_eq!(bytes.len(), message.as_bytes().len());
+    /// ```
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns this message's header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let header = MessageRef::parse(&bytes).unwrap().header();
+    /// ```
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Returns this message's auto id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let auto_id = MessageRef::parse(&bytes).unwrap().auto_id();
+    /// ```
+    pub fn auto_id(&self) -> u32 {
+        self.header.auto_id
+    }
+
+    /// Returns this message's imei.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// assert_eq!("300234063904190", MessageRef::parse(&bytes).unwrap().imei());
+    /// ```
+    pub fn imei(&self) -> Imei {
+        self.header.imei
+    }
+
+    /// Returns this message's session status.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let session_status = MessageRef::parse(&bytes).unwrap().session_status();
+    /// ```
+    pub fn session_status(&self) -> SessionStatus {
+        self.header.session_status
+    }
+
+    /// Returns this message's mobile originated message sequence number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let momsn = MessageRef::parse(&bytes).unwrap().momsn();
+    /// ```
+    pub fn momsn(&self) -> u16 {
+        self.header.momsn
+    }
+
+    /// Returns this message's mobile terminated message sequence number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let mtmsn = MessageRef::parse(&bytes).unwrap().mtmsn();
+    /// ```
+    pub fn mtmsn(&self) -> u16 {
+        self.header.mtmsn
+    }
+
+    /// Returns this message's time of session.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let time_of_session = MessageRef::parse(&bytes).unwrap().time_of_session();
+    /// ```
+    pub fn time_of_session(&self) -> DateTime<Utc> {
+        self.header.time_of_session
+    }
+
+    /// Returns this message's payload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let payload = MessageRef::parse(&bytes).unwrap().payload();
+    /// ```
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// Returns an iterator over all of this message's information elements, including the header
+    /// and the payload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::MessageRef;
+    /// let bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+    /// let message = MessageRef::parse(&bytes).unwrap();
+    /// let ieis: Vec<u8> = message.information_elements().map(|ie| ie.iei()).collect();
+    /// assert_eq!(vec![1, 3, 2], ieis);
+    /// ```
+    pub fn information_elements(&self) -> InformationElementRefs<'a> {
+        InformationElementRefs {
+            bytes: self.bytes,
+            offset: 3,
+            options: self.options,
+        }
+    }
+
+    /// Copies this message into an owned `Message`.
+    ///
+    /// If duplicate headers or payloads were allowed while parsing, only the first of each is
+    /// kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{Message, MessageRef};
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let message = MessageRef::parse(&bytes).unwrap().to_message();
+    /// assert_eq!(Message::from_path("data/0-mo.sbd").unwrap(), message);
+    /// ```
+    pub fn to_message(&self) -> Message {
+        // Everything has already been validated (and warned about), so we don't need to check
+        // again.
+        let options = ParseOptions::all(Policy::Ignore);
+        let information_elements = self
+            .information_elements()
+            .filter(|ie| ie.iei != 1 && ie.iei != 2)
+            .filter_map(|ie| InformationElement::decode(ie.iei, ie.bytes, &options).ok())
+            .collect();
+        Message::from_parts(self.header, self.payload.to_vec(), information_elements)
+    }
+}
+
+impl From<MessageRef<'_>> for Message {
+    fn from(message: MessageRef<'_>) -> Message {
+        message.to_message()
+    }
+}
+
+impl<'a> InformationElementRef<'a> {
+    /// Returns the information element identifier.
+    pub fn iei(&self) -> u8 {
+        self.iei
+    }
+
+    /// Returns the offset of this information element from the start of the message.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the contents of this information element, not including the identifier or length.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Decodes this information element into an owned `InformationElement`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{InformationElement, MessageRef};
+    /// let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+    /// let message = MessageRef::parse(&bytes).unwrap();
+    /// let header = message.information_elements().next().unwrap();
+    /// assert_eq!(
+    ///     InformationElement::Header(message.header()),
+    ///     header.to_information_element().unwrap()
+    /// );
+    /// ```
+    pub fn to_information_element(&self) -> Result<InformationElement, Error> {
+        InformationElement::decode(self.iei, self.bytes, &ParseOptions::default())
+            .map_err(|err| self.error(err))
+    }
+
+    /// Reads the information element that starts at `offset` of a message.
+    fn next(
+        bytes: &'a [u8],
+        offset: usize,
+        options: &ParseOptions,
+    ) -> Result<InformationElementRef<'a>, Error> {
+        let iei = bytes[offset];
+        let error = |err| Error::InformationElement {
+            iei,
+            offset,
+            source: Box::new(err),
+        };
+        let available = bytes.len() - offset;
+        if available < 3 {
+            return Err(error(Error::UnexpectedEnd {
+                needed: 3,
+                available,
+            }));
+        }
+        let declared = BigEndian::read_u16(&bytes[offset + 1..offset + 3]);
+        let length = body_length(iei, declared, options).map_err(error)?;
+        if available - 3 < length {
+            return Err(error(Error::UnexpectedEnd {
+                needed: length,
+                available: available - 3,
+            }));
+        }
+        Ok(InformationElementRef {
+            iei,
+            offset,
+            bytes: &bytes[offset + 3..offset + 3 + length],
+        })
+    }
+
+    fn len(&self) -> usize {
+        3 + self.bytes.len()
+    }
+
+    fn error(&self, err: Error) -> Error {
+        Error::InformationElement {
+            iei: self.iei,
+            offset: self.offset,
+            source: Box::new(err),
+        }
+    }
+
+    /// Checks this information element without allocating, recording the header and payload.
+    fn validate(
+        &self,
+        header: &mut Option<Header>,
+        payload: &mut Option<&'a [u8]>,
+        options: &ParseOptions,
+    ) -> Result<(), Error> {
+        match self.iei {
+            1 => {
+                let h = decode_header(self.bytes, options)?;
+                if let Some(header) = *header {
+                    options
+                        .duplicate_information_elements
+                        .apply(Error::TwoHeaders(h, header))?;
+                } else {
+                    *header = Some(h);
+                }
+            }
+            2 => {
+                if let Some(payload) = *payload {
+                    options
+                        .duplicate_information_elements
+                        .apply(Error::TwoPayloads(self.bytes.to_vec(), payload.to_vec()))?;
+                } else {
+                    *payload = Some(self.bytes);
+                }
+            }
+            3 | 5 => {
+                let _ = InformationElement::decode(self.iei, self.bytes, options)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for InformationElementRefs<'a> {
+    type Item = InformationElementRef<'a>;
+
+    fn next(&mut self) -> Option<InformationElementRef<'a>> {
+        if self.offset >= self.bytes.len() {
+            return None;
+        }
+        match InformationElementRef::next(self.bytes, self.offset, &self.options) {
+            Ok(information_element) => {
+                self.offset += information_element.len();
+                Some(information_element)
+            }
+            Err(_) => {
+                self.offset = self.bytes.len();
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        let message = MessageRef::parse(&bytes).unwrap();
+        assert_eq!("301434061799480", message.imei());
+        assert_eq!(7, message.momsn());
+        assert_eq!(46, message.payload().len());
+        assert_eq!(
+            Message::from_path("data/2-location.mo.sbd").unwrap(),
+            message.to_message()
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        for i in 0..bytes.len() {
+            assert!(MessageRef::parse(&bytes[..i]).is_err());
+        }
+    }
+
+    #[test]
+    fn information_element_offsets() {
+        let bytes = std::fs::read("data/2-location.mo.sbd").unwrap();
+        let message = MessageRef::parse(&bytes).unwrap();
+        let offsets: Vec<usize> = message
+            .information_elements()
+            .map(|ie| ie.offset())
+            .collect();
+        assert_eq!(vec![3, 34, 48], offsets);
+    }
+
+    #[test]
+    fn invalid_information_element_length() {
+        let mut bytes = std::fs::read("data/0-mo.sbd").unwrap();
+        bytes[35] = 0xff;
+        match MessageRef::parse(&bytes).unwrap_err() {
+            Error::InformationElement { iei, offset, .. } => {
+                assert_eq!(2, iei);
+                assert_eq!(34, offset);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+}
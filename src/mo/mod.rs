@@ -1,15 +1,47 @@
 //! Module for reading and writing Mobile-Originated (MO) SBD messages.
 //!
-//! Though messages technically come in two flavors, mobile originated and mobile terminated, we
-//! only handle mobile originated messages in this library.
+//! Messages come in two flavors, mobile originated and mobile terminated. Mobile terminated
+//! messages are in the `mt` module.
 
+mod builder;
+#[cfg(feature = "tokio")]
+mod codec;
+#[cfg(feature = "serde")]
+mod encoding;
+mod explain;
+#[cfg(feature = "std")]
+mod geo;
 mod header;
 mod information_element;
 mod location;
 mod message;
+mod message_ref;
+mod parse_options;
+#[cfg(feature = "std")]
+mod reader;
 mod session_status;
+#[cfg(test)]
+mod strategy;
 
+#[cfg(feature = "tokio")]
+pub use self::codec::MoCodec;
+#[cfg(feature = "serde")]
+pub use self::encoding::{PayloadEncoding, WithPayloadEncoding};
+#[cfg(feature = "std")]
+pub(crate) use self::information_element::read_onto;
 pub use self::{
-    header::Header, information_element::InformationElement, message::Message,
+    builder::{MessageBuilder, MAX_PAYLOAD_LENGTH},
+    explain::Annotation,
+    header::Header,
+    information_element::InformationElement,
+    location::{MoLocation, MoLocationError},
+    message::{Message, PROTOCOL_REVISION_NUMBER},
+    message_ref::{InformationElementRef, InformationElementRefs, MessageRef},
+    parse_options::{ParseOptions, Policy},
     session_status::SessionStatus,
 };
+#[cfg(feature = "std")]
+pub use self::{
+    geo::{Feature, EARTH_RADIUS_KM},
+    reader::MessageReader,
+};
@@ -0,0 +1,143 @@
+//! Control how strictly mobile-originated messages are parsed.
+//!
+//! The Iridium gateway is usually well-behaved, but archives of SBD messages can contain all
+//! sorts of oddities. `ParseOptions` lets callers decide, for each kind of anomaly, whether to
+//! reject the message or to log a warning and keep going.
+
+use log::warn;
+
+use crate::Error;
+
+/// What to do when the parser encounters an anomaly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Policy {
+    /// Return an error.
+    Fail,
+    /// Log a warning and continue parsing.
+    Warn,
+    /// Don't check for the anomaly at all.
+    Ignore,
+}
+
+/// Options for parsing mobile-originated messages.
+///
+/// The default options match the behavior of `Message::read_from`.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mo::{Message, ParseOptions, Policy};
+/// let mut options = ParseOptions::strict();
+/// options.trailing_bytes = Policy::Warn;
+/// let message = Message::read_from_with(std::fs::File::open("data/0-mo.sbd").unwrap(), &options)
+///     .unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseOptions {
+    /// What to do with a session status code that isn't in the specification.
+    ///
+    /// If the message is kept, the code is stored as `SessionStatus::Other`. By default, unknown
+    /// codes are kept without a warning, since the gateway may add codes at any time.
+    pub unknown_session_status: Policy,
+    /// What to do with bytes after the end of the message, as declared by the overall message
+    /// length.
+    ///
+    /// Checking for trailing bytes reads the source until it ends or until about one more
+    /// message's worth of bytes has been read, so don't enable this check on a stream that won't
+    /// be closed by the other side.
+    pub trailing_bytes: Policy,
+    /// What to do if a fixed-size information element (e.g. the header or the location) declares
+    /// a longer length than its fixed size.
+    ///
+    /// If the message is kept, the fields are read from the start of the information element and
+    /// the extra bytes are skipped. A declared length that's too short is always an error.
+    pub information_element_length: Policy,
+    /// What to do if the location information can't be parsed into a `MoLocation`.
+    ///
+    /// If the message is kept, the raw location bytes are kept as well.
+    pub invalid_location: Policy,
+    /// What to do if there are two headers or two payloads.
+    ///
+    /// If the message is kept, the first header or payload wins.
+    pub duplicate_information_elements: Policy,
+}
+
+impl ParseOptions {
+    /// Returns options that fail on every anomaly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{ParseOptions, Policy};
+    /// let options = ParseOptions::strict();
+    /// assert_eq!(Policy::Fail, options.trailing_bytes);
+    /// ```
+    pub fn strict() -> ParseOptions {
+        ParseOptions::all(Policy::Fail)
+    }
+
+    /// Returns options that warn on every anomaly, salvaging as much of the message as possible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::{ParseOptions, Policy};
+    /// let options = ParseOptions::lenient();
+    /// assert_eq!(Policy::Warn, options.trailing_bytes);
+    /// ```
+    pub fn lenient() -> ParseOptions {
+        ParseOptions::all(Policy::Warn)
+    }
+
+    pub(crate) fn all(policy: Policy) -> ParseOptions {
+        ParseOptions {
+            unknown_session_status: policy,
+            trailing_bytes: policy,
+            information_element_length: policy,
+            invalid_location: policy,
+            duplicate_information_elements: policy,
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            unknown_session_status: Policy::Ignore,
+            trailing_bytes: Policy::Ignore,
+            information_element_length: Policy::Fail,
+            invalid_location: Policy::Ignore,
+            duplicate_information_elements: Policy::Fail,
+        }
+    }
+}
+
+impl Policy {
+    /// Applies this policy to an anomaly, described by `err`.
+    ///
+    /// Returns the error if we should fail, otherwise returns `Ok` (possibly after logging).
+    pub(crate) fn apply(self, err: Error) -> Result<(), Error> {
+        match self {
+            Policy::Fail => Err(err),
+            Policy::Warn => {
+                warn!("{}, continuing", err);
+                Ok(())
+            }
+            Policy::Ignore => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        assert!(Policy::Fail.apply(Error::NoHeader).is_err());
+        assert!(Policy::Warn.apply(Error::NoHeader).is_ok());
+        assert!(Policy::Ignore.apply(Error::NoHeader).is_ok());
+    }
+}
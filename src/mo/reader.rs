@@ -0,0 +1,304 @@
+//! Read many mobile-originated messages from a single stream.
+
+use std::io::{ErrorKind, Read};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::{
+    mo::{Message, MessageRef, ParseOptions, PROTOCOL_REVISION_NUMBER},
+    Error,
+};
+
+/// An iterator over mobile-originated messages that have been concatenated into one stream.
+///
+/// The iterator ends when the stream ends cleanly, i.e. right after the last byte of a message.
+/// If the stream ends partway through a message, the iterator yields an
+/// `Error::UnexpectedEnd` before ending.
+///
+/// By default, a message that is framed correctly but fails to parse is yielded as an error and
+/// skipped, and the iterator ends after an error that breaks the framing (e.g. a bad protocol
+/// revision number). If resynchronization is enabled, the iterator instead searches forward
+/// from the start of a bad message for the next protocol revision number byte that begins a
+/// valid message. Only the first error is yielded while resynchronizing, and any bytes at the
+/// end of the stream that never resynchronize are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use sbd::mo::MessageReader;
+/// let file = File::open("data/0-mo.sbd").unwrap();
+/// let messages = MessageReader::new(file).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(1, messages.len());
+/// ```
+#[derive(Debug)]
+pub struct MessageReader<R: Read> {
+    read: R,
+    buffer: Vec<u8>,
+    options: ParseOptions,
+    resynchronize: bool,
+    resynchronizing: bool,
+    done: bool,
+}
+
+enum Frame {
+    Message(Message),
+    End,
+    Invalid(Error, Option<usize>),
+    Fatal(Error),
+}
+
+impl<R: Read> MessageReader<R> {
+    /// Creates a new reader, using the default `ParseOptions` and no resynchronization.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use sbd::mo::MessageReader;
+    /// let reader = MessageReader::new(File::open("data/0-mo.sbd").unwrap());
+    /// ```
+    pub fn new(read: R) -> MessageReader<R> {
+        MessageReader {
+            read,
+            buffer: Vec::new(),
+            options: ParseOptions::default(),
+            resynchronize: false,
+            resynchronizing: false,
+            done: false,
+        }
+    }
+
+    /// Sets the options used to parse each message.
+    ///
+    /// The trailing bytes option is not used, since the bytes after one message are the next
+    /// message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use sbd::mo::{MessageReader, ParseOptions};
+    /// let reader = MessageReader::new(File::open("data/0-mo.sbd").unwrap())
+    ///     .parse_options(ParseOptions::lenient());
+    /// ```
+    pub fn parse_options(mut self, options: ParseOptions) -> MessageReader<R> {
+        self.options = options;
+        self
+    }
+
+    /// Sets whether to resynchronize after a corrupt message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use sbd::mo::MessageReader;
+    /// let reader = MessageReader::new(File::open("data/0-mo.sbd").unwrap()).resynchronize(true);
+    /// ```
+    pub fn resynchronize(mut self, resynchronize: bool) -> MessageReader<R> {
+        self.resynchronize = resynchronize;
+        self
+    }
+
+    /// Consumes this reader, returning the underlying stream.
+    ///
+    /// Any bytes that have been read from the stream but not yet parsed are lost.
+    pub fn into_inner(self) -> R {
+        self.read
+    }
+
+    /// Reads from the stream until there are at least `n` bytes in the buffer.
+    ///
+    /// Returns false if the stream ends first.
+    fn fill(&mut self, n: usize) -> Result<bool, Error> {
+        while self.buffer.len() < n {
+            let needed = (n - self.buffer.len()) as u64;
+            match Read::by_ref(&mut self.read)
+                .take(needed)
+                .read_to_end(&mut self.buffer)
+            {
+                Ok(0) => return Ok(false),
+                Ok(_) => {}
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_frame(&mut self) -> Frame {
+        match self.fill(3) {
+            Ok(true) => {}
+            Ok(false) => {
+                return if self.buffer.is_empty() || self.resynchronizing {
+                    Frame::End
+                } else {
+                    Frame::Fatal(Error::UnexpectedEnd {
+                        needed: 3,
+                        available: self.buffer.len(),
+                    })
+                };
+            }
+            Err(err) => return Frame::Fatal(err),
+        }
+        if self.buffer[0] != PROTOCOL_REVISION_NUMBER {
+            return Frame::Invalid(Error::InvalidProtocolRevisionNumber(self.buffer[0]), None);
+        }
+        let length = 3 + usize::from(BigEndian::read_u16(&self.buffer[1..3]));
+        match self.fill(length) {
+            Ok(true) => {}
+            Ok(false) => {
+                let err = Error::UnexpectedEnd {
+                    needed: length,
+                    available: self.buffer.len(),
+                };
+                return if self.resynchronizing {
+                    Frame::Invalid(err, None)
+                } else {
+                    Frame::Fatal(err)
+                };
+            }
+            Err(err) => return Frame::Fatal(err),
+        }
+        let frame = match MessageRef::parse_with(&self.buffer[..length], &self.options) {
+            Ok(message) => Frame::Message(message.to_message()),
+            Err(err) => return Frame::Invalid(err, Some(length)),
+        };
+        self.buffer.drain(..length);
+        frame
+    }
+
+    /// Drops the first byte of the buffer and everything up to the next protocol revision number.
+    fn skip_to_next_protocol_revision_number(&mut self) {
+        let position = self
+            .buffer
+            .iter()
+            .skip(1)
+            .position(|&b| b == PROTOCOL_REVISION_NUMBER)
+            .map(|position| position + 1)
+            .unwrap_or(self.buffer.len());
+        self.buffer.drain(..position);
+    }
+}
+
+impl<R: Read> Iterator for MessageReader<R> {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Result<Message, Error>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            match self.read_frame() {
+                Frame::Message(message) => {
+                    self.resynchronizing = false;
+                    return Some(Ok(message));
+                }
+                Frame::End => {
+                    self.done = true;
+                    return None;
+                }
+                Frame::Fatal(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                Frame::Invalid(err, length) => {
+                    if self.resynchronize {
+                        self.skip_to_next_protocol_revision_number();
+                        if !self.resynchronizing {
+                            self.resynchronizing = true;
+                            return Some(Err(err));
+                        }
+                    } else {
+                        match length {
+                            Some(length) => {
+                                self.buffer.drain(..length);
+                            }
+                            None => self.done = true,
+                        }
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = fs::read("data/0-mo.sbd").unwrap();
+        bytes.extend(fs::read("data/2-location.mo.sbd").unwrap());
+        bytes
+    }
+
+    #[test]
+    fn read_two() {
+        let messages = MessageReader::new(&bytes()[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(2, messages.len());
+        assert_eq!(75, messages[0].momsn());
+        assert_eq!(7, messages[1].momsn());
+    }
+
+    #[test]
+    fn empty() {
+        assert!(MessageReader::new(&[][..]).next().is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = bytes();
+        let mut reader = MessageReader::new(&bytes[..bytes.len() - 1]);
+        assert!(reader.next().unwrap().is_ok());
+        match reader.next().unwrap().unwrap_err() {
+            Error::UnexpectedEnd { needed, available } => {
+                assert_eq!(97, needed);
+                assert_eq!(96, available);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn skip_bad_message() {
+        let mut bytes = bytes();
+        bytes[10] = 0xff; // invalid imei
+        let mut reader = MessageReader::new(&bytes[..]);
+        assert!(reader.next().unwrap().is_err());
+        assert_eq!(7, reader.next().unwrap().unwrap().momsn());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn garbage_without_resynchronize() {
+        let mut bytes = fs::read("data/0-mo.sbd").unwrap();
+        bytes.extend(b"garbage");
+        bytes.extend(fs::read("data/2-location.mo.sbd").unwrap());
+        let mut reader = MessageReader::new(&bytes[..]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn garbage_with_resynchronize() {
+        let mut bytes = fs::read("data/0-mo.sbd").unwrap();
+        bytes.extend([0x01, 0x00, 0x05, 0xff, 0x01, 0x01]);
+        bytes.extend(fs::read("data/2-location.mo.sbd").unwrap());
+        bytes.extend(b"trailing garbage \x01");
+        let mut reader = MessageReader::new(&bytes[..]).resynchronize(true);
+        assert_eq!(75, reader.next().unwrap().unwrap().momsn());
+        assert!(reader.next().unwrap().is_err());
+        assert_eq!(7, reader.next().unwrap().unwrap().momsn());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
@@ -1,38 +1,56 @@
-use serde::Serialize;
+use core::fmt;
 
 use crate::Error;
 
 /// The status of a mobile-originated session.
 ///
 /// The descriptions for these codes are taken directly from the `DirectIP` documentation.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+///
+/// Codes that aren't in the specification are kept as `SessionStatus::Other`, so they can be
+/// written back unchanged. Session statuses are compared by code, so `Other(0)` is equal to `Ok`
+/// and is treated the same way.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mo::SessionStatus;
+/// let status = SessionStatus::from(13);
+/// assert_eq!(SessionStatus::RFLinkLoss, status);
+/// assert!(status.is_transfer_failure());
+/// assert_eq!("An RF link loss occurred during the SBD session.", status.description());
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SessionStatus {
     /// The SBD session completed successfully.
-    Ok = 0,
+    Ok,
     /// The MO message transfer, if any, was successful. The MT message queued at the GSS is too
     /// large to be transferred within a single SBD session.
-    OkMobileTerminatedTooLarge = 1,
+    OkMobileTerminatedTooLarge,
     /// The MO message transfer, if any, was successful. The reported location was determined to be
     /// of unacceptable quality. This value is only applicable to IMEIs using SBD protocol revision
     /// 1.
-    OkLocationUnacceptableQuality = 2,
+    OkLocationUnacceptableQuality,
     /// The SBD session timed out before session completion.
-    Timeout = 10,
+    Timeout,
     /// The MO message being transferred by the IMEI is too large to be transferred within a single
     /// SBD session.
-    MobileOriginatedTooLarge = 12,
+    MobileOriginatedTooLarge,
     /// An RF link loss ocurred during the SBD session.
-    RFLinkLoss = 13,
+    RFLinkLoss,
     /// An IMEI protocol anomaly occurred during SBD session.
-    IMEIProtocolAnomaly = 14,
+    IMEIProtocolAnomaly,
     /// The IMEI is prohibited from accessing the GSS.
-    Prohibited = 15,
+    Prohibited,
+    /// A session status code that isn't in the specification.
+    Other(u8),
 }
 
 impl SessionStatus {
     /// Creates a new session status from a code.
     ///
-    /// Returns an error if the code is unknown.
+    /// Returns an error if the code is unknown. Use `SessionStatus::from` to keep unknown codes
+    /// as `SessionStatus::Other`.
     ///
     /// # Examples
     ///
@@ -54,4 +72,183 @@ impl SessionStatus {
             _ => Err(Error::UnknownSessionStatus(n)),
         }
     }
+
+    /// Returns the numeric code for this session status.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::SessionStatus;
+    /// assert_eq!(10, SessionStatus::Timeout.code());
+    /// assert_eq!(3, SessionStatus::Other(3).code());
+    /// ```
+    pub fn code(&self) -> u8 {
+        match *self {
+            SessionStatus::Ok => 0,
+            SessionStatus::OkMobileTerminatedTooLarge => 1,
+            SessionStatus::OkLocationUnacceptableQuality => 2,
+            SessionStatus::Timeout => 10,
+            SessionStatus::MobileOriginatedTooLarge => 12,
+            SessionStatus::RFLinkLoss => 13,
+            SessionStatus::IMEIProtocolAnomaly => 14,
+            SessionStatus::Prohibited => 15,
+            SessionStatus::Other(n) => n,
+        }
+    }
+
+    /// Returns true if the MO message transfer, if any, was successful.
+    ///
+    /// The session might still have had a problem, e.g. `OkMobileTerminatedTooLarge`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::SessionStatus;
+    /// assert!(SessionStatus::Ok.is_success());
+    /// assert!(SessionStatus::OkMobileTerminatedTooLarge.is_success());
+    /// assert!(!SessionStatus::Timeout.is_success());
+    /// assert!(!SessionStatus::Other(3).is_success());
+    /// ```
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self.normalize(),
+            SessionStatus::Ok
+                | SessionStatus::OkMobileTerminatedTooLarge
+                | SessionStatus::OkLocationUnacceptableQuality
+        )
+    }
+
+    /// Returns true if the session failed to transfer the MO message.
+    ///
+    /// Unknown codes are neither successes nor transfer failures.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::SessionStatus;
+    /// assert!(SessionStatus::Timeout.is_transfer_failure());
+    /// assert!(!SessionStatus::Ok.is_transfer_failure());
+    /// assert!(!SessionStatus::Other(3).is_transfer_failure());
+    /// ```
+    pub fn is_transfer_failure(&self) -> bool {
+        matches!(
+            self.normalize(),
+            SessionStatus::Timeout
+                | SessionStatus::MobileOriginatedTooLarge
+                | SessionStatus::RFLinkLoss
+                | SessionStatus::IMEIProtocolAnomaly
+                | SessionStatus::Prohibited
+        )
+    }
+
+    /// Returns the description of this session status from the Developers Guide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mo::SessionStatus;
+    /// assert_eq!(
+    ///     "The SBD session completed successfully.",
+    ///     SessionStatus::Ok.description()
+    /// );
+    /// ```
+    pub fn description(&self) -> &'static str {
+        match self.normalize() {
+            SessionStatus::Ok => "The SBD session completed successfully.",
+            SessionStatus::OkMobileTerminatedTooLarge => {
+                "The MO message transfer, if any, was successful. The MT message queued at the GSS \
+                 is too large to be transferred within a single SBD session."
+            }
+            SessionStatus::OkLocationUnacceptableQuality => {
+                "The MO message transfer, if any, was successful. The reported location was \
+                 determined to be of unacceptable quality."
+            }
+            SessionStatus::Timeout => "The SBD session timed out before session completion.",
+            SessionStatus::MobileOriginatedTooLarge => {
+                "The MO message being transferred by the IMEI is too large to be transferred \
+                 within a single SBD session."
+            }
+            SessionStatus::RFLinkLoss => "An RF link loss occurred during the SBD session.",
+            SessionStatus::IMEIProtocolAnomaly => {
+                "An IMEI protocol anomaly occurred during SBD session."
+            }
+            SessionStatus::Prohibited => "The IMEI is prohibited from accessing the GSS.",
+            SessionStatus::Other(_) => "The session status code is not in the specification.",
+        }
+    }
+
+    /// Turns an `Other` that holds a code from the specification into that code's variant.
+    fn normalize(self) -> SessionStatus {
+        SessionStatus::from(self.code())
+    }
+}
+
+impl PartialEq for SessionStatus {
+    fn eq(&self, other: &SessionStatus) -> bool {
+        self.code() == other.code()
+    }
+}
+
+impl Eq for SessionStatus {}
+
+impl From<u8> for SessionStatus {
+    fn from(n: u8) -> SessionStatus {
+        SessionStatus::new(n).unwrap_or(SessionStatus::Other(n))
+    }
+}
+
+impl From<SessionStatus> for u8 {
+    fn from(session_status: SessionStatus) -> u8 {
+        session_status.code()
+    }
+}
+
+impl fmt::Display for SessionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.description(), self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for n in 0..=u8::MAX {
+            let session_status = SessionStatus::from(n);
+            assert_eq!(n, session_status.code());
+            assert_eq!(
+                SessionStatus::new(n).is_ok(),
+                !matches!(session_status, SessionStatus::Other(_))
+            );
+        }
+    }
+
+    #[test]
+    fn other_with_a_known_code() {
+        assert_eq!(SessionStatus::Ok, SessionStatus::Other(0));
+        assert_ne!(SessionStatus::Ok, SessionStatus::Other(3));
+        assert!(SessionStatus::Other(0).is_success());
+        assert!(SessionStatus::Other(13).is_transfer_failure());
+        assert_eq!(
+            SessionStatus::RFLinkLoss.description(),
+            SessionStatus::Other(13).description()
+        );
+    }
+
+    #[test]
+    fn classification() {
+        for n in 0..=u8::MAX {
+            let session_status = SessionStatus::from(n);
+            assert!(!(session_status.is_success() && session_status.is_transfer_failure()));
+            if let SessionStatus::Other(_) = session_status {
+                assert!(!session_status.is_success());
+                assert!(!session_status.is_transfer_failure());
+            } else {
+                assert_eq!(n < 10, session_status.is_success());
+                assert_eq!(n >= 10, session_status.is_transfer_failure());
+            }
+        }
+    }
 }
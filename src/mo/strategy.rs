@@ -0,0 +1,97 @@
+//! Proptest strategies for generating mobile-originated messages.
+
+use chrono::{DateTime, TimeZone, Utc};
+use proptest::{collection::vec, prelude::*};
+
+use crate::{
+    mo::{Header, InformationElement, Message, MoLocation, SessionStatus, MAX_PAYLOAD_LENGTH},
+    Imei,
+};
+
+pub fn imei() -> impl Strategy<Value = Imei> {
+    prop::array::uniform15(b'0'..=b'9').prop_map(|bytes| Imei::new(bytes).unwrap())
+}
+
+pub fn time_of_session() -> impl Strategy<Value = DateTime<Utc>> {
+    any::<u32>().prop_map(|timestamp| Utc.timestamp_opt(i64::from(timestamp), 0).unwrap())
+}
+
+pub fn header() -> impl Strategy<Value = Header> {
+    (
+        any::<u32>(),
+        imei(),
+        any::<u8>(),
+        any::<u16>(),
+        any::<u16>(),
+        time_of_session(),
+    )
+        .prop_map(
+            |(auto_id, imei, session_status, momsn, mtmsn, time_of_session)| Header {
+                auto_id,
+                imei,
+                session_status: SessionStatus::from(session_status),
+                momsn,
+                mtmsn,
+                time_of_session,
+            },
+        )
+}
+
+pub fn location() -> impl Strategy<Value = MoLocation> {
+    (
+        any::<bool>(),
+        any::<bool>(),
+        0..=90u8,
+        0..60_000u16,
+        0..=180u8,
+        0..60_000u16,
+        any::<u32>(),
+    )
+        .prop_map(
+            |(north, east, lat_deg, lat_thousandths_min, lon_deg, lon_thousandths_min, cep_km)| {
+                MoLocation {
+                    north,
+                    east,
+                    lat_deg,
+                    lat_thousandths_min,
+                    lon_deg,
+                    lon_thousandths_min,
+                    cep_km,
+                }
+            },
+        )
+}
+
+/// Information elements other than the header and the payload.
+pub fn other_information_element() -> impl Strategy<Value = InformationElement> {
+    prop_oneof![
+        location().prop_map(InformationElement::from),
+        any::<[u8; 11]>().prop_map(InformationElement::LocationInformation),
+        any::<bool>().prop_map(InformationElement::Confirmation),
+        (
+            any::<u8>().prop_filter("known information element", |iei| ![1, 2, 3, 5]
+                .contains(iei)),
+            vec(any::<u8>(), 0..64)
+        )
+            .prop_map(|(iei, bytes)| InformationElement::Unknown { iei, bytes }),
+    ]
+}
+
+pub fn information_element() -> impl Strategy<Value = InformationElement> {
+    prop_oneof![
+        header().prop_map(InformationElement::Header),
+        vec(any::<u8>(), 0..=MAX_PAYLOAD_LENGTH).prop_map(InformationElement::Payload),
+        other_information_element(),
+    ]
+}
+
+pub fn message() -> impl Strategy<Value = Message> {
+    (
+        header(),
+        vec(any::<u8>(), 0..=MAX_PAYLOAD_LENGTH),
+        vec(other_information_element(), 0..4),
+    )
+        .prop_map(|(header, payload, information_elements)| {
+            Message::from_parts(header, payload, information_elements)
+        })
+}
@@ -0,0 +1,352 @@
+//! Send mobile-terminated messages to an Iridium gateway through `DirectIP`.
+//!
+//! Each message is sent on its own TCP connection. The client writes the MT message, then the
+//! gateway replies with an MT confirmation and closes the connection.
+
+use std::{
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+use log::{debug, info, warn};
+
+use crate::{
+    mt::{Confirmation, Message},
+    Error,
+};
+
+/// A `DirectIP` client that sends MT messages to a gateway.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use sbd::mt::{Client, DispositionFlags, Header, Message};
+/// let client = Client::new("12.47.179.12:10800")
+///     .unwrap()
+///     .read_timeout(Some(Duration::from_secs(60)));
+/// let header = Header {
+///     unique_client_message_id: 1,
+///     imei: "300234063904190".parse().unwrap(),
+///     disposition_flags: DispositionFlags::empty(),
+/// };
+/// let message = Message::new(vec![header.into(), b"hello".to_vec().into()]).unwrap();
+/// let confirmation = client.send(&message).unwrap();
+/// if !confirmation.status.is_success() {
+///     eprintln!("gateway rejected the message: {}", confirmation.status);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Client {
+    addrs: Vec<SocketAddr>,
+    connect_timeout: Duration,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+}
+
+/// How a `Client` retries when it can't connect to the gateway.
+///
+/// Only connection failures are retried. Once the message has been written, the gateway may have
+/// queued it, so a failure to read the confirmation is returned instead of sending the message
+/// again.
+///
+/// Between attempts the client sleeps, starting at `initial_backoff` and doubling each time up to
+/// `max_backoff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt.
+    pub max_retries: u32,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The longest delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl Client {
+    /// Creates a new client for the gateway at `addr`.
+    ///
+    /// The address is resolved now, but no connection is made until `send`. The default connect,
+    /// read, and write timeouts are thirty seconds, and the default retry policy is
+    /// `RetryPolicy::default()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let client = sbd::mt::Client::new("127.0.0.1:10800").unwrap();
+    /// ```
+    pub fn new<A: ToSocketAddrs>(addr: A) -> Result<Client, Error> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve any gateway addresses",
+            )
+            .into());
+        }
+        Ok(Client {
+            addrs,
+            connect_timeout: Duration::from_secs(30),
+            read_timeout: Some(Duration::from_secs(30)),
+            write_timeout: Some(Duration::from_secs(30)),
+            retry_policy: RetryPolicy::default(),
+        })
+    }
+
+    /// Sets the timeout for each connection attempt.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Client {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout for reading the confirmation, or `None` to wait forever.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Client {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout for writing the message, or `None` to wait forever.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Client {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Sets the retry policy for connection failures.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Client {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sends a message to the gateway and returns its confirmation.
+    ///
+    /// A confirmation is returned whether or not the gateway accepted the message; check
+    /// `Confirmation::status`. Errors are returned if the client can't connect (after retrying),
+    /// can't write the message, or doesn't get back a valid confirmation for this message.
+    pub fn send(&self, message: &Message) -> Result<Confirmation, Error> {
+        let mut bytes = vec![0; message.encoded_len()];
+        let _ = message.encode_into(&mut bytes)?;
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(self.read_timeout)?;
+        stream.set_write_timeout(self.write_timeout)?;
+        stream.write_all(&bytes)?;
+        stream.flush()?;
+        let confirmation = Confirmation::read_from(&mut stream)?;
+        if confirmation.unique_client_message_id != message.unique_client_message_id()
+            || confirmation.imei != message.imei()
+        {
+            return Err(Error::ConfirmationMismatch {
+                sent_id: message.unique_client_message_id(),
+                sent_imei: message.imei(),
+                confirmed_id: confirmation.unique_client_message_id,
+                confirmed_imei: confirmation.imei,
+            });
+        }
+        info!(
+            "Sent message {} to IMEI {}: {}",
+            confirmation.unique_client_message_id, confirmation.imei, confirmation.status
+        );
+        Ok(confirmation)
+    }
+
+    fn connect(&self) -> Result<TcpStream, Error> {
+        let mut backoff = self.retry_policy.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            match self.connect_once() {
+                Ok(stream) => return Ok(stream),
+                Err(err) if attempt < self.retry_policy.max_retries => {
+                    attempt += 1;
+                    warn!(
+                        "Could not connect to gateway, retrying in {:?} ({}/{}): {}",
+                        backoff, attempt, self.retry_policy.max_retries, err
+                    );
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(self.retry_policy.max_backoff);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn connect_once(&self) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in &self.addrs {
+            debug!("Connecting to gateway at {}", addr);
+            match TcpStream::connect_timeout(addr, self.connect_timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.expect("a client always has at least one address"))
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::RetryPolicy;
+    /// assert_eq!(0, RetryPolicy::none().max_retries);
+    /// ```
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, net::TcpListener, time::Instant};
+
+    use super::*;
+    use crate::mt::{fixtures::mt_message, ConfirmationStatus, DispositionFlags};
+
+    /// Accepts one connection, reads a message, and replies with a confirmation.
+    fn gateway(listener: TcpListener, status: ConfirmationStatus) -> thread::JoinHandle<Message> {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let message = Message::read_from(&mut stream).unwrap();
+            Confirmation {
+                unique_client_message_id: message.unique_client_message_id(),
+                imei: message.imei(),
+                auto_id: 1234,
+                status,
+            }
+            .write_to(&mut stream)
+            .unwrap();
+            message
+        })
+    }
+
+    fn fast_retries(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+        }
+    }
+
+    #[test]
+    fn send() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = Client::new(listener.local_addr().unwrap()).unwrap();
+        let handle = gateway(listener, ConfirmationStatus::Queued(1));
+        let confirmation = client
+            .send(&mt_message(42, DispositionFlags::empty()))
+            .unwrap();
+        assert_eq!(42, confirmation.unique_client_message_id);
+        assert_eq!(1234, confirmation.auto_id);
+        assert_eq!(ConfirmationStatus::Queued(1), confirmation.status);
+        assert_eq!(
+            mt_message(42, DispositionFlags::empty()),
+            handle.join().unwrap()
+        );
+    }
+
+    #[test]
+    fn rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = Client::new(listener.local_addr().unwrap()).unwrap();
+        let handle = gateway(listener, ConfirmationStatus::QueueFull);
+        let confirmation = client
+            .send(&mt_message(42, DispositionFlags::empty()))
+            .unwrap();
+        assert_eq!(ConfirmationStatus::QueueFull, confirmation.status);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connection_refused() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = Client::new(addr).unwrap().retry_policy(fast_retries(2));
+        let start = Instant::now();
+        assert!(matches!(
+            client.send(&mt_message(42, DispositionFlags::empty())),
+            Err(Error::Io(_))
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn retry_until_gateway_is_up() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            gateway(
+                TcpListener::bind(addr).unwrap(),
+                ConfirmationStatus::Queued(1),
+            )
+            .join()
+            .unwrap()
+        });
+        let client = Client::new(addr).unwrap().retry_policy(fast_retries(20));
+        assert!(client
+            .send(&mt_message(42, DispositionFlags::empty()))
+            .unwrap()
+            .status
+            .is_success());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = Client::new(listener.local_addr().unwrap())
+            .unwrap()
+            .read_timeout(Some(Duration::from_millis(50)));
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut bytes = Vec::new();
+            let _ = stream.read_to_end(&mut bytes);
+        });
+        match client
+            .send(&mt_message(42, DispositionFlags::empty()))
+            .unwrap_err()
+        {
+            Error::Io(err) => assert!(matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            )),
+            err => panic!("unexpected error: {}", err),
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn garbage_reply() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = Client::new(listener.local_addr().unwrap()).unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            Message::read_from(&mut stream).unwrap();
+            stream.write_all(b"garbage").unwrap();
+        });
+        assert!(matches!(
+            client.send(&mt_message(42, DispositionFlags::empty())),
+            Err(Error::InvalidProtocolRevisionNumber(b'g'))
+        ));
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,445 @@
+//! The confirmation the gateway sends back after receiving a mobile-terminated message.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use byteorder::{BigEndian, ByteOrder};
+
+#[cfg(feature = "std")]
+use crate::mt::information_element::read_message;
+use crate::{mo::PROTOCOL_REVISION_NUMBER, mt::information_element::split, Error, Imei};
+
+const CONFIRMATION_IEI: u8 = 0x44;
+const CONFIRMATION_LENGTH: u16 = 25;
+const MAX_QUEUE_POSITION: u8 = 50;
+
+/// The confirmation of a mobile-terminated message, sent by the gateway.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mt::{Confirmation, ConfirmationStatus};
+/// let mut bytes = vec![0x01, 0x00, 0x1c, 0x44, 0x00, 0x19, 0x4d, 0x73, 0x67, 0x31];
+/// bytes.extend_from_slice(b"300034010123450");
+/// bytes.extend_from_slice(&[0x00, 0x00, 0xe4, 0x69, 0x00, 0x32]);
+/// let confirmation = Confirmation::read_from(&bytes[..]).unwrap();
+/// assert_eq!(ConfirmationStatus::Queued(50), confirmation.status);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Confirmation {
+    /// The unique client message id from the header of the MT message.
+    pub unique_client_message_id: u32,
+    /// The IMEI from the header of the MT message.
+    pub imei: Imei,
+    /// The gateway's id for the MT message, or zero if the message wasn't queued.
+    pub auto_id: u32,
+    /// The outcome.
+    pub status: ConfirmationStatus,
+}
+
+/// The status of a mobile-terminated message, as reported in its confirmation.
+///
+/// The descriptions for these codes are taken directly from the `DirectIP` documentation.
+///
+/// Codes that aren't in the specification are kept as `ConfirmationStatus::Other`, so they can be
+/// written back unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mt::ConfirmationStatus;
+/// let status = ConfirmationStatus::from(-5);
+/// assert_eq!(ConfirmationStatus::QueueFull, status);
+/// assert!(!status.is_success());
+/// assert_eq!(Some(3), ConfirmationStatus::from(3).queue_position());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConfirmationStatus {
+    /// Successful, with the order of the message in the MT message queue, from 1 to 50.
+    ///
+    /// Confirmations with a queue position outside of that range can't be encoded.
+    Queued(u8),
+    /// Successful, no payload in message.
+    SuccessNoPayload,
+    /// Invalid IMEI: too few characters, or non-numeric characters.
+    InvalidImei,
+    /// Unknown IMEI: not provisioned on the GSS.
+    UnknownImei,
+    /// Payload size exceeded the maximum allowed.
+    PayloadSizeExceeded,
+    /// Payload expected, but none received.
+    PayloadExpected,
+    /// MT message queue full (max of 50).
+    QueueFull,
+    /// MT resources unavailable.
+    ResourcesUnavailable,
+    /// Violation of the MT `DirectIP` protocol.
+    ProtocolError,
+    /// Ring alerts to the given IMEI are disabled.
+    RingAlertsDisabled,
+    /// The given IMEI is not attached, i.e. not set to receive ring alerts.
+    ImeiNotAttached,
+    /// Source IP address rejected by the MT filter.
+    SourceIpRejected,
+    /// The MTMSN value is out of range (the valid range is 1 to 65,535).
+    MtmsnOutOfRange,
+    /// A status code that isn't in the specification.
+    Other(i16),
+}
+
+impl Confirmation {
+    /// Reads a confirmation message from an object that implements `Read`.
+    #[cfg(feature = "std")]
+    pub fn read_from<R: Read>(read: R) -> Result<Confirmation, Error> {
+        Confirmation::decode(&read_message(read)?)
+    }
+
+    /// Decodes a confirmation message from the start of a byte slice.
+    ///
+    /// The message must hold exactly one MT confirmation information element.
+    pub fn decode(bytes: &[u8]) -> Result<Confirmation, Error> {
+        let mut confirmation = None;
+        for raw in split(bytes)? {
+            if raw.iei != CONFIRMATION_IEI {
+                return Err(raw.error(Error::InvalidInformationElementIdentifier(raw.iei)));
+            }
+            if confirmation.is_some() {
+                return Err(raw.error(Error::DuplicateInformationElement(raw.iei)));
+            }
+            let body = raw.bytes;
+            if body.len() != usize::from(CONFIRMATION_LENGTH) {
+                return Err(raw.error(Error::InformationElementLength {
+                    iei: raw.iei,
+                    expected: CONFIRMATION_LENGTH,
+                    actual: body.len() as u16,
+                }));
+            }
+            let mut imei = [0; 15];
+            imei.copy_from_slice(&body[4..19]);
+            confirmation = Some(Confirmation {
+                unique_client_message_id: BigEndian::read_u32(&body[0..4]),
+                imei: Imei::new(imei).map_err(|err| raw.error(err))?,
+                auto_id: BigEndian::read_u32(&body[19..23]),
+                status: ConfirmationStatus::from(BigEndian::read_i16(&body[23..25])),
+            });
+        }
+        confirmation.ok_or(Error::MissingInformationElement(CONFIRMATION_IEI))
+    }
+
+    /// Writes this confirmation message to an object that implements `Write`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::{Confirmation, ConfirmationStatus};
+    /// let confirmation = Confirmation {
+    ///     unique_client_message_id: 1,
+    ///     imei: "300234063904190".parse().unwrap(),
+    ///     auto_id: 0,
+    ///     status: ConfirmationStatus::UnknownImei,
+    /// };
+    /// let mut bytes = Vec::new();
+    /// confirmation.write_to(&mut bytes).unwrap();
+    /// assert_eq!(confirmation, Confirmation::read_from(&bytes[..]).unwrap());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn write_to<W: Write>(&self, mut write: W) -> Result<(), Error> {
+        let mut bytes = [0; 31];
+        let _ = self.encode_into(&mut bytes)?;
+        write.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Returns the number of bytes needed to encode this confirmation message.
+    pub fn encoded_len(&self) -> usize {
+        6 + usize::from(CONFIRMATION_LENGTH)
+    }
+
+    /// Encodes this confirmation message into the start of a byte slice.
+    ///
+    /// Returns the number of bytes written, or an error if the buffer is smaller than
+    /// `encoded_len()` or the status is `Queued` with a position outside of 1 to 50.
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        if let ConfirmationStatus::Queued(n) = self.status {
+            if !(1..=MAX_QUEUE_POSITION).contains(&n) {
+                return Err(Error::InvalidQueuePosition(n));
+            }
+        }
+        let needed = self.encoded_len();
+        if buffer.len() < needed {
+            return Err(Error::BufferTooSmall {
+                needed,
+                available: buffer.len(),
+            });
+        }
+        buffer[0] = PROTOCOL_REVISION_NUMBER;
+        BigEndian::write_u16(&mut buffer[1..3], 3 + CONFIRMATION_LENGTH);
+        buffer[3] = CONFIRMATION_IEI;
+        BigEndian::write_u16(&mut buffer[4..6], CONFIRMATION_LENGTH);
+        BigEndian::write_u32(&mut buffer[6..10], self.unique_client_message_id);
+        buffer[10..25].copy_from_slice(self.imei.as_bytes());
+        BigEndian::write_u32(&mut buffer[25..29], self.auto_id);
+        BigEndian::write_i16(&mut buffer[29..31], self.status.code());
+        Ok(needed)
+    }
+}
+
+impl ConfirmationStatus {
+    /// Returns the numeric code for this status.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::ConfirmationStatus;
+    /// assert_eq!(-2, ConfirmationStatus::UnknownImei.code());
+    /// assert_eq!(12, ConfirmationStatus::Queued(12).code());
+    /// ```
+    pub fn code(&self) -> i16 {
+        match *self {
+            ConfirmationStatus::Queued(n) => i16::from(n),
+            ConfirmationStatus::SuccessNoPayload => 0,
+            ConfirmationStatus::InvalidImei => -1,
+            ConfirmationStatus::UnknownImei => -2,
+            ConfirmationStatus::PayloadSizeExceeded => -3,
+            ConfirmationStatus::PayloadExpected => -4,
+            ConfirmationStatus::QueueFull => -5,
+            ConfirmationStatus::ResourcesUnavailable => -6,
+            ConfirmationStatus::ProtocolError => -7,
+            ConfirmationStatus::RingAlertsDisabled => -8,
+            ConfirmationStatus::ImeiNotAttached => -9,
+            ConfirmationStatus::SourceIpRejected => -10,
+            ConfirmationStatus::MtmsnOutOfRange => -11,
+            ConfirmationStatus::Other(n) => n,
+        }
+    }
+
+    /// Returns true if the gateway accepted the message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::ConfirmationStatus;
+    /// assert!(ConfirmationStatus::Queued(1).is_success());
+    /// assert!(ConfirmationStatus::SuccessNoPayload.is_success());
+    /// assert!(!ConfirmationStatus::QueueFull.is_success());
+    /// assert!(!ConfirmationStatus::Other(51).is_success());
+    /// ```
+    pub fn is_success(&self) -> bool {
+        matches!(
+            *self,
+            ConfirmationStatus::Queued(_) | ConfirmationStatus::SuccessNoPayload
+        )
+    }
+
+    /// Returns true if the gateway turned the message away for now, but might accept it later.
+    ///
+    /// The queue may drain and resources may free up, so these messages are worth sending again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::ConfirmationStatus;
+    /// assert!(ConfirmationStatus::QueueFull.is_transient());
+    /// assert!(ConfirmationStatus::ResourcesUnavailable.is_transient());
+    /// assert!(!ConfirmationStatus::UnknownImei.is_transient());
+    /// assert!(!ConfirmationStatus::Queued(1).is_transient());
+    /// ```
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            *self,
+            ConfirmationStatus::QueueFull | ConfirmationStatus::ResourcesUnavailable
+        )
+    }
+
+    /// Returns the position of the message in the MT message queue, if it was queued.
+    pub fn queue_position(&self) -> Option<u8> {
+        match *self {
+            ConfirmationStatus::Queued(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns the description of this status from the Developers Guide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::ConfirmationStatus;
+    /// assert_eq!(
+    ///     "MT message queue full (max of 50)",
+    ///     ConfirmationStatus::QueueFull.description()
+    /// );
+    /// ```
+    pub fn description(&self) -> &'static str {
+        match *self {
+            ConfirmationStatus::Queued(_) => "Successful, order of message in the MT message queue",
+            ConfirmationStatus::SuccessNoPayload => "Successful, no payload in message",
+            ConfirmationStatus::InvalidImei => {
+                "Invalid IMEI – too few characters, non-numeric characters"
+            }
+            ConfirmationStatus::UnknownImei => "Unknown IMEI – not provisioned on the GSS",
+            ConfirmationStatus::PayloadSizeExceeded => "Payload size exceeded maximum allowed",
+            ConfirmationStatus::PayloadExpected => "Payload expected, but none received",
+            ConfirmationStatus::QueueFull => "MT message queue full (max of 50)",
+            ConfirmationStatus::ResourcesUnavailable => "MT resources unavailable",
+            ConfirmationStatus::ProtocolError => "Violation of MT DirectIP protocol error",
+            ConfirmationStatus::RingAlertsDisabled => "Ring alerts to the given IMEI are disabled",
+            ConfirmationStatus::ImeiNotAttached => {
+                "The given IMEI is not attached (not set to receive ring alerts)"
+            }
+            ConfirmationStatus::SourceIpRejected => "Source IP address rejected by MT filter",
+            ConfirmationStatus::MtmsnOutOfRange => {
+                "MTMSN value is out of range (valid range is 1 – 65,535)"
+            }
+            ConfirmationStatus::Other(_) => "The status code is not in the specification.",
+        }
+    }
+}
+
+impl From<i16> for ConfirmationStatus {
+    fn from(n: i16) -> ConfirmationStatus {
+        match n {
+            1..=50 => ConfirmationStatus::Queued(n as u8),
+            0 => ConfirmationStatus::SuccessNoPayload,
+            -1 => ConfirmationStatus::InvalidImei,
+            -2 => ConfirmationStatus::UnknownImei,
+            -3 => ConfirmationStatus::PayloadSizeExceeded,
+            -4 => ConfirmationStatus::PayloadExpected,
+            -5 => ConfirmationStatus::QueueFull,
+            -6 => ConfirmationStatus::ResourcesUnavailable,
+            -7 => ConfirmationStatus::ProtocolError,
+            -8 => ConfirmationStatus::RingAlertsDisabled,
+            -9 => ConfirmationStatus::ImeiNotAttached,
+            -10 => ConfirmationStatus::SourceIpRejected,
+            -11 => ConfirmationStatus::MtmsnOutOfRange,
+            n => ConfirmationStatus::Other(n),
+        }
+    }
+}
+
+impl From<ConfirmationStatus> for i16 {
+    fn from(status: ConfirmationStatus) -> i16 {
+        status.code()
+    }
+}
+
+impl fmt::Display for ConfirmationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.description(), self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example confirmation from the Developers Guide.
+    fn bytes() -> Vec<u8> {
+        let mut bytes = vec![0x01, 0x00, 0x1c, 0x44, 0x00, 0x19, 0x4d, 0x73, 0x67, 0x31];
+        bytes.extend_from_slice(b"300034010123450");
+        bytes.extend_from_slice(&[0x00, 0x00, 0xe4, 0x69, 0x00, 0x32]);
+        bytes
+    }
+
+    #[test]
+    fn read_from() {
+        let confirmation = Confirmation::read_from(&bytes()[..]).unwrap();
+        assert_eq!(0x4d736731, confirmation.unique_client_message_id);
+        assert_eq!("300034010123450", confirmation.imei);
+        assert_eq!(58473, confirmation.auto_id);
+        assert_eq!(ConfirmationStatus::Queued(50), confirmation.status);
+    }
+
+    #[test]
+    fn write_to() {
+        let confirmation = Confirmation::decode(&bytes()).unwrap();
+        let mut written = Vec::new();
+        confirmation.write_to(&mut written).unwrap();
+        assert_eq!(bytes(), written);
+        assert_eq!(written.len(), confirmation.encoded_len());
+    }
+
+    #[test]
+    fn queue_position_out_of_range() {
+        let mut confirmation = Confirmation::decode(&bytes()).unwrap();
+        for n in [0, 51, u8::MAX] {
+            confirmation.status = ConfirmationStatus::Queued(n);
+            assert!(matches!(
+                confirmation.encode_into(&mut [0; 31]),
+                Err(Error::InvalidQueuePosition(m)) if m == n
+            ));
+        }
+        confirmation.status = ConfirmationStatus::Queued(50);
+        assert!(confirmation.encode_into(&mut [0; 31]).is_ok());
+    }
+
+    #[test]
+    fn negative_status() {
+        let mut bytes = bytes();
+        bytes[29..31].copy_from_slice(&(-11i16).to_be_bytes());
+        let confirmation = Confirmation::decode(&bytes).unwrap();
+        assert_eq!(ConfirmationStatus::MtmsnOutOfRange, confirmation.status);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut wrong_iei = bytes();
+        wrong_iei[3] = 0x41;
+        assert!(matches!(
+            Confirmation::decode(&wrong_iei),
+            Err(Error::InformationElement {
+                iei: 0x41,
+                offset: 3,
+                ..
+            })
+        ));
+
+        let mut wrong_length = bytes();
+        wrong_length[2] = 0x1b;
+        wrong_length[5] = 0x18;
+        assert!(matches!(
+            Confirmation::decode(&wrong_length),
+            Err(Error::InformationElement {
+                iei: 0x44,
+                offset: 3,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            Confirmation::decode(&[1, 0, 0]),
+            Err(Error::MissingInformationElement(0x44))
+        ));
+
+        let mut truncated = bytes();
+        truncated.pop();
+        assert!(matches!(
+            Confirmation::decode(&truncated),
+            Err(Error::UnexpectedEnd {
+                needed: 31,
+                available: 30
+            })
+        ));
+
+        let bytes = bytes();
+        for i in 0..bytes.len() {
+            assert!(Confirmation::read_from(&bytes[..i]).is_err());
+        }
+    }
+
+    #[test]
+    fn status_roundtrip() {
+        for n in i16::MIN..=i16::MAX {
+            let status = ConfirmationStatus::from(n);
+            assert_eq!(n, status.code());
+            assert_eq!((0..=50).contains(&n), status.is_success());
+            assert_eq!(
+                (-11..=50).contains(&n),
+                !matches!(status, ConfirmationStatus::Other(_))
+            );
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Disposition flags and priority, which tell the gateway how to handle a mobile-terminated
+//! message.
+
+use core::{cmp::Ordering, fmt};
+
+use bitflags::bitflags;
+
+use crate::Error;
+
+bitflags! {
+    /// The disposition flags in a mobile-terminated header.
+    ///
+    /// Bits that aren't in the specification are preserved, so they can be written back
+    /// unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::DispositionFlags;
+    /// let flags = DispositionFlags::FLUSH_MT_QUEUE | DispositionFlags::HIGH_PRIORITY_MESSAGE;
+    /// assert_eq!(17, flags.bits());
+    /// assert!(flags.contains(DispositionFlags::FLUSH_MT_QUEUE));
+    /// ```
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct DispositionFlags: u16 {
+        /// Delete all MT payloads in the device's MT queue.
+        const FLUSH_MT_QUEUE = 1;
+        /// Send a ring alert to the device even though there is no MT message.
+        const SEND_RING_ALERT_NO_MTM = 2;
+        /// Update the SSD location with the location of the device.
+        const UPDATE_SSD_LOCATION = 8;
+        /// Place the message at the head of the device's MT queue.
+        const HIGH_PRIORITY_MESSAGE = 16;
+        /// Use the unique client message id as the MTMSN.
+        const ASSIGN_MTMSN = 32;
+    }
+}
+
+impl DispositionFlags {
+    /// Returns true if these flags let a message be sent without a payload.
+    ///
+    /// Flushing the MT queue and sending a ring alert with no MTM don't deliver a payload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::DispositionFlags;
+    /// assert!(DispositionFlags::FLUSH_MT_QUEUE.allows_no_payload());
+    /// assert!(!DispositionFlags::HIGH_PRIORITY_MESSAGE.allows_no_payload());
+    /// ```
+    pub fn allows_no_payload(&self) -> bool {
+        self.intersects(DispositionFlags::FLUSH_MT_QUEUE | DispositionFlags::SEND_RING_ALERT_NO_MTM)
+    }
+}
+
+/// The priority of a mobile-terminated message in the device's MT queue.
+///
+/// Levels go from 1, the highest, to 5, the lowest. Messages without a priority information
+/// element are queued at the lowest priority.
+///
+/// Priorities compare by urgency, not by level, so a higher priority is greater.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mt::Priority;
+/// let priority = Priority::new(1).unwrap();
+/// assert_eq!(1, priority.level());
+/// assert_eq!(5, Priority::default().level());
+/// assert!(Priority::new(6).is_err());
+/// assert!(Priority::HIGHEST > Priority::LOWEST);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u16", into = "u16"))]
+pub struct Priority(u16);
+
+impl Priority {
+    /// The highest priority.
+    pub const HIGHEST: Priority = Priority(1);
+
+    /// The lowest priority, which is the default.
+    pub const LOWEST: Priority = Priority(5);
+
+    /// Creates a new priority from a level between 1 and 5.
+    pub fn new(level: u16) -> Result<Priority, Error> {
+        if (1..=5).contains(&level) {
+            Ok(Priority(level))
+        } else {
+            Err(Error::InvalidPriority(level))
+        }
+    }
+
+    /// Returns the priority level, from 1 to 5.
+    pub fn level(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Priority {
+        Priority::LOWEST
+    }
+}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Priority) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Priority) -> Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
+impl TryFrom<u16> for Priority {
+    type Error = Error;
+
+    fn try_from(level: u16) -> Result<Priority, Error> {
+        Priority::new(level)
+    }
+}
+
+impl From<Priority> for u16 {
+    fn from(priority: Priority) -> u16 {
+        priority.0
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags() {
+        assert_eq!(1, DispositionFlags::FLUSH_MT_QUEUE.bits());
+        assert_eq!(2, DispositionFlags::SEND_RING_ALERT_NO_MTM.bits());
+        assert_eq!(8, DispositionFlags::UPDATE_SSD_LOCATION.bits());
+        assert_eq!(16, DispositionFlags::HIGH_PRIORITY_MESSAGE.bits());
+        assert_eq!(32, DispositionFlags::ASSIGN_MTMSN.bits());
+        assert_eq!(0xffff, DispositionFlags::from_bits_retain(0xffff).bits());
+    }
+
+    #[test]
+    fn priority() {
+        assert!(Priority::new(0).is_err());
+        for level in 1..=5 {
+            assert_eq!(level, Priority::new(level).unwrap().level());
+        }
+        assert!(Priority::new(6).is_err());
+        assert!(Priority::HIGHEST > Priority::LOWEST);
+        let mut priorities: Vec<_> = (1..=5).map(|level| Priority::new(level).unwrap()).collect();
+        priorities.sort();
+        assert_eq!(Priority::LOWEST, priorities[0]);
+        assert_eq!(Priority::HIGHEST, priorities[4]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        assert_eq!(
+            "3",
+            serde_json::to_string(&Priority::new(3).unwrap()).unwrap()
+        );
+        assert!(serde_json::from_str::<Priority>("0").is_err());
+        let flags = DispositionFlags::FLUSH_MT_QUEUE | DispositionFlags::ASSIGN_MTMSN;
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(flags, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,44 @@
+//! Mobile-terminated messages and clients shared by tests.
+
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+#[cfg(feature = "std")]
+use crate::{
+    mt::{Client, RetryPolicy},
+    testing::MockGateway,
+};
+use crate::{
+    mt::{DispositionFlags, Header, Message},
+    Imei,
+};
+
+pub const IMEI: &str = "300234063904190";
+
+pub fn imei() -> Imei {
+    IMEI.parse().unwrap()
+}
+
+pub fn header(unique_client_message_id: u32, disposition_flags: DispositionFlags) -> Header {
+    Header {
+        unique_client_message_id,
+        imei: imei(),
+        disposition_flags,
+    }
+}
+
+pub fn mt_message(unique_client_message_id: u32, disposition_flags: DispositionFlags) -> Message {
+    Message::new(vec![
+        header(unique_client_message_id, disposition_flags).into(),
+        b"hello".to_vec().into(),
+    ])
+    .unwrap()
+}
+
+#[cfg(feature = "std")]
+pub fn client(gateway: &MockGateway) -> Client {
+    Client::new(gateway.addr())
+        .unwrap()
+        .retry_policy(RetryPolicy::none())
+        .read_timeout(Some(Duration::from_secs(5)))
+}
@@ -0,0 +1,15 @@
+use crate::{mt::DispositionFlags, Imei};
+
+/// A mobile-terminated header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Header {
+    /// A message id chosen by the vendor application, echoed back in the confirmation.
+    ///
+    /// The gateway doesn't interpret this value, and it is often four ASCII characters.
+    pub unique_client_message_id: u32,
+    /// The destination device id.
+    pub imei: Imei,
+    /// The disposition flags, which tell the gateway what to do with the message.
+    pub disposition_flags: DispositionFlags,
+}
@@ -0,0 +1,357 @@
+//! Information elements of mobile-terminated messages.
+
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+#[cfg(feature = "std")]
+use byteorder::ReadBytesExt;
+use byteorder::{BigEndian, ByteOrder};
+
+#[cfg(feature = "std")]
+use crate::mo::read_onto;
+use crate::{
+    mo::PROTOCOL_REVISION_NUMBER,
+    mt::{DispositionFlags, Header, Priority},
+    Error, Imei,
+};
+
+pub(crate) const HEADER_IEI: u8 = 0x41;
+pub(crate) const PAYLOAD_IEI: u8 = 0x42;
+pub(crate) const PRIORITY_IEI: u8 = 0x46;
+const HEADER_LENGTH: u16 = 21;
+const PRIORITY_LENGTH: u16 = 2;
+
+/// A mobile-terminated information element, or IE.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum InformationElement {
+    /// Information element holding the mobile-terminated header.
+    Header(Header),
+    /// The mobile-terminated payload.
+    Payload(Vec<u8>),
+    /// The priority of the message in the device's MT queue.
+    Priority(Priority),
+}
+
+impl InformationElement {
+    /// Decodes an information element from its identifier and the bytes after its length field.
+    pub(crate) fn decode(iei: u8, bytes: &[u8]) -> Result<InformationElement, Error> {
+        match iei {
+            HEADER_IEI => {
+                if bytes.len() != usize::from(HEADER_LENGTH) {
+                    return Err(Error::InformationElementLength {
+                        iei,
+                        expected: HEADER_LENGTH,
+                        actual: bytes.len() as u16,
+                    });
+                }
+                let mut imei = [0; 15];
+                imei.copy_from_slice(&bytes[4..19]);
+                Ok(InformationElement::Header(Header {
+                    unique_client_message_id: BigEndian::read_u32(&bytes[0..4]),
+                    imei: Imei::new(imei)?,
+                    disposition_flags: DispositionFlags::from_bits_retain(BigEndian::read_u16(
+                        &bytes[19..21],
+                    )),
+                }))
+            }
+            PRIORITY_IEI => {
+                if bytes.len() != usize::from(PRIORITY_LENGTH) {
+                    return Err(Error::InformationElementLength {
+                        iei,
+                        expected: PRIORITY_LENGTH,
+                        actual: bytes.len() as u16,
+                    });
+                }
+                Ok(InformationElement::Priority(Priority::new(
+                    BigEndian::read_u16(bytes),
+                )?))
+            }
+            PAYLOAD_IEI => Ok(InformationElement::Payload(bytes.to_vec())),
+            _ => Err(Error::InvalidInformationElementIdentifier(iei)),
+        }
+    }
+
+    /// Returns the length of this information element, including the information element
+    /// header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::InformationElement;
+    /// assert_eq!(8, InformationElement::Payload(b"hello".to_vec()).len());
+    /// ```
+    pub fn len(&self) -> usize {
+        3 + match *self {
+            InformationElement::Header(_) => usize::from(HEADER_LENGTH),
+            InformationElement::Payload(ref payload) => payload.len(),
+            InformationElement::Priority(_) => usize::from(PRIORITY_LENGTH),
+        }
+    }
+
+    /// Returns true if this information element is empty.
+    ///
+    /// An information element is never empty, since it always has an identifier and a length.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Writes this information element to a `Write`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::InformationElement;
+    /// let mut bytes = Vec::new();
+    /// InformationElement::Payload(b"hi".to_vec()).write_to(&mut bytes).unwrap();
+    /// assert_eq!(vec![0x42, 0, 2, b'h', b'i'], bytes);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn write_to<W: Write>(&self, mut write: W) -> Result<(), Error> {
+        let mut bytes = vec![0; self.len()];
+        let _ = self.encode_into(&mut bytes)?;
+        write.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Encodes this information element into the start of a byte slice.
+    ///
+    /// Returns the number of bytes written.
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let len = self.len();
+        if buffer.len() < len {
+            return Err(Error::BufferTooSmall {
+                needed: len,
+                available: buffer.len(),
+            });
+        }
+        match *self {
+            InformationElement::Header(ref header) => {
+                buffer[0] = HEADER_IEI;
+                BigEndian::write_u16(&mut buffer[1..3], HEADER_LENGTH);
+                BigEndian::write_u32(&mut buffer[3..7], header.unique_client_message_id);
+                buffer[7..22].copy_from_slice(header.imei.as_bytes());
+                BigEndian::write_u16(&mut buffer[22..24], header.disposition_flags.bits());
+            }
+            InformationElement::Payload(ref payload) => {
+                let length = u16::try_from(payload.len())
+                    .map_err(|_| Error::PayloadTooLong(payload.len()))?;
+                buffer[0] = PAYLOAD_IEI;
+                BigEndian::write_u16(&mut buffer[1..3], length);
+                buffer[3..len].copy_from_slice(payload);
+            }
+            InformationElement::Priority(priority) => {
+                buffer[0] = PRIORITY_IEI;
+                BigEndian::write_u16(&mut buffer[1..3], PRIORITY_LENGTH);
+                BigEndian::write_u16(&mut buffer[3..5], priority.level());
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// Reads the bytes of exactly one message, as declared by its overall message length.
+#[cfg(feature = "std")]
+pub(crate) fn read_message<R: Read>(mut read: R) -> Result<Vec<u8>, Error> {
+    let protocol_revision_number = read.read_u8()?;
+    if protocol_revision_number != PROTOCOL_REVISION_NUMBER {
+        return Err(Error::InvalidProtocolRevisionNumber(
+            protocol_revision_number,
+        ));
+    }
+    let overall_message_length = read.read_u16::<BigEndian>()?;
+    let mut bytes = vec![protocol_revision_number, 0, 0];
+    BigEndian::write_u16(&mut bytes[1..3], overall_message_length);
+    read_onto(&mut read, usize::from(overall_message_length), &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Splits a message into its information elements.
+///
+/// Any bytes after the end of the message are ignored.
+pub(crate) fn split(bytes: &[u8]) -> Result<Vec<RawInformationElement<'_>>, Error> {
+    if bytes.len() < 3 {
+        return Err(Error::UnexpectedEnd {
+            needed: 3,
+            available: bytes.len(),
+        });
+    }
+    if bytes[0] != PROTOCOL_REVISION_NUMBER {
+        return Err(Error::InvalidProtocolRevisionNumber(bytes[0]));
+    }
+    let end = 3 + usize::from(BigEndian::read_u16(&bytes[1..3]));
+    if bytes.len() < end {
+        return Err(Error::UnexpectedEnd {
+            needed: end,
+            available: bytes.len(),
+        });
+    }
+    let mut information_elements = Vec::new();
+    let mut offset = 3;
+    while offset < end {
+        let iei = bytes[offset];
+        if end - offset < 3 {
+            return Err(in_information_element(
+                iei,
+                offset,
+                Error::UnexpectedEnd {
+                    needed: 3,
+                    available: end - offset,
+                },
+            ));
+        }
+        let length = usize::from(BigEndian::read_u16(&bytes[offset + 1..offset + 3]));
+        let body = offset + 3;
+        if end - body < length {
+            return Err(in_information_element(
+                iei,
+                offset,
+                Error::UnexpectedEnd {
+                    needed: length,
+                    available: end - body,
+                },
+            ));
+        }
+        information_elements.push(RawInformationElement {
+            offset,
+            iei,
+            bytes: &bytes[body..body + length],
+        });
+        offset = body + length;
+    }
+    Ok(information_elements)
+}
+
+/// An information element that has been split out of a message, but not yet decoded.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RawInformationElement<'a> {
+    /// The offset of the information element from the start of the message.
+    pub(crate) offset: usize,
+    /// The information element identifier.
+    pub(crate) iei: u8,
+    /// The contents of the information element, not including the identifier or length.
+    pub(crate) bytes: &'a [u8],
+}
+
+impl RawInformationElement<'_> {
+    /// Wraps an error with the identifier and offset of this information element.
+    pub(crate) fn error(&self, source: Error) -> Error {
+        in_information_element(self.iei, self.offset, source)
+    }
+}
+
+fn in_information_element(iei: u8, offset: usize, source: Error) -> Error {
+    Error::InformationElement {
+        iei,
+        offset,
+        source: Box::new(source),
+    }
+}
+
+impl From<Header> for InformationElement {
+    fn from(header: Header) -> InformationElement {
+        InformationElement::Header(header)
+    }
+}
+
+impl From<Priority> for InformationElement {
+    fn from(priority: Priority) -> InformationElement {
+        InformationElement::Priority(priority)
+    }
+}
+
+impl From<Vec<u8>> for InformationElement {
+    fn from(payload: Vec<u8>) -> InformationElement {
+        InformationElement::Payload(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header {
+            unique_client_message_id: 0x4d736731,
+            imei: "300034010123450".parse().unwrap(),
+            disposition_flags: DispositionFlags::empty(),
+        }
+    }
+
+    #[test]
+    fn roundtrip_header() {
+        let information_element = InformationElement::Header(header());
+        let mut bytes = [0; 24];
+        assert_eq!(24, information_element.encode_into(&mut bytes).unwrap());
+        assert_eq!([0x41, 0, 21, b'M', b's', b'g', b'1'], bytes[..7]);
+        assert_eq!(
+            information_element,
+            InformationElement::decode(bytes[0], &bytes[3..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn header_length() {
+        assert!(matches!(
+            InformationElement::decode(0x41, &[0; 20]).unwrap_err(),
+            Error::InformationElementLength {
+                iei: 0x41,
+                expected: 21,
+                actual: 20
+            }
+        ));
+    }
+
+    #[test]
+    fn roundtrip_priority() {
+        let information_element = InformationElement::Priority(Priority::HIGHEST);
+        let mut bytes = [0; 5];
+        assert_eq!(5, information_element.encode_into(&mut bytes).unwrap());
+        assert_eq!([0x46, 0, 2, 0, 1], bytes);
+        assert_eq!(
+            information_element,
+            InformationElement::decode(bytes[0], &bytes[3..]).unwrap()
+        );
+        assert!(matches!(
+            InformationElement::decode(0x46, &[0, 6]).unwrap_err(),
+            Error::InvalidPriority(6)
+        ));
+        assert!(InformationElement::decode(0x46, &[0, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn unknown_disposition_flags() {
+        let mut bytes = [0; 24];
+        InformationElement::Header(header())
+            .encode_into(&mut bytes)
+            .unwrap();
+        bytes[22..24].copy_from_slice(&[0x80, 0x04]);
+        match InformationElement::decode(bytes[0], &bytes[3..]).unwrap() {
+            InformationElement::Header(header) => {
+                assert_eq!(0x8004, header.disposition_flags.bits())
+            }
+            information_element => panic!("unexpected: {:?}", information_element),
+        }
+    }
+
+    #[test]
+    fn unknown_iei() {
+        assert!(matches!(
+            InformationElement::decode(0x01, &[]).unwrap_err(),
+            Error::InvalidInformationElementIdentifier(0x01)
+        ));
+    }
+
+    #[test]
+    fn small_buffer() {
+        let mut bytes = [0; 23];
+        assert!(InformationElement::Header(header())
+            .encode_into(&mut bytes)
+            .is_err());
+    }
+}
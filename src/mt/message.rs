@@ -0,0 +1,440 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use byteorder::{BigEndian, ByteOrder};
+
+#[cfg(feature = "std")]
+use crate::mt::information_element::read_message;
+use crate::{
+    mo::PROTOCOL_REVISION_NUMBER,
+    mt::{
+        information_element::{split, HEADER_IEI, PAYLOAD_IEI, PRIORITY_IEI},
+        DispositionFlags, Header, InformationElement, Priority,
+    },
+    Error, Imei,
+};
+
+/// The maximum length of a mobile-terminated payload, in bytes.
+///
+/// This is the largest MT message supported by any Iridium transceiver, per the Developers
+/// Guide. Smaller transceivers (e.g. the 9601 and 9602) only support 270 bytes.
+pub const MAX_PAYLOAD_LENGTH: usize = 1890;
+
+/// A mobile-terminated Iridium SBD message, sent to the gateway for delivery to a device.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mt::{DispositionFlags, Header, Message};
+/// let header = Header {
+///     unique_client_message_id: 1,
+///     imei: "300234063904190".parse().unwrap(),
+///     disposition_flags: DispositionFlags::empty(),
+/// };
+/// let message = Message::new(vec![header.into(), b"hello".to_vec().into()]).unwrap();
+/// let mut bytes = Vec::new();
+/// message.write_to(&mut bytes).unwrap();
+/// assert_eq!(message, Message::read_from(&bytes[..]).unwrap());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Message {
+    header: Header,
+    payload: Vec<u8>,
+    priority: Option<Priority>,
+}
+
+impl Message {
+    /// Reads in a message from an object that implements `Read`.
+    ///
+    /// Exactly one message is read, so any bytes after the message are left in the reader.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::Message;
+    /// let mut bytes = vec![1, 0, 32, 0x41, 0, 21, 0, 0, 0, 1];
+    /// bytes.extend_from_slice(b"300234063904190");
+    /// bytes.extend_from_slice(&[0, 0, 0x42, 0, 5]);
+    /// bytes.extend_from_slice(b"hello");
+    /// let message = Message::read_from(&bytes[..]).unwrap();
+    /// assert_eq!(b"hello", message.payload().as_slice());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn read_from<R: Read>(read: R) -> Result<Message, Error> {
+        Message::decode(&read_message(read)?)
+    }
+
+    /// Decodes a message from the start of a byte slice.
+    ///
+    /// This is the slice-based counterpart to `Message::read_from`, and is available without the
+    /// `std` feature. Any bytes after the end of the message are ignored.
+    ///
+    /// Errors in information elements are reported as `Error::InformationElement`, which
+    /// includes the byte offset of the offending information element.
+    pub fn decode(bytes: &[u8]) -> Result<Message, Error> {
+        let information_elements = split(bytes)?
+            .into_iter()
+            .map(|raw| InformationElement::decode(raw.iei, raw.bytes).map_err(|err| raw.error(err)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Message::new(information_elements)
+    }
+
+    /// Creates a new message from information elements.
+    ///
+    /// A message needs exactly one header and one payload, and the payload must be between one
+    /// and `MAX_PAYLOAD_LENGTH` bytes long. The payload can be left out if the disposition flags
+    /// flush the MT queue or send a ring alert with no MTM. A priority is optional.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::{DispositionFlags, Header, Message};
+    /// let header = Header {
+    ///     unique_client_message_id: 1,
+    ///     imei: "300234063904190".parse().unwrap(),
+    ///     disposition_flags: DispositionFlags::empty(),
+    /// };
+    /// assert!(Message::new(vec![header.into()]).is_err());
+    /// assert!(Message::new(vec![header.into(), vec![0; 1891].into()]).is_err());
+    /// ```
+    pub fn new<I: IntoIterator<Item = InformationElement>>(iter: I) -> Result<Message, Error> {
+        let mut header: Option<Header> = None;
+        let mut payload = None;
+        let mut priority = None;
+        for information_element in iter {
+            match information_element {
+                InformationElement::Header(h) => {
+                    if header.replace(h).is_some() {
+                        return Err(Error::DuplicateInformationElement(HEADER_IEI));
+                    }
+                }
+                InformationElement::Payload(p) => {
+                    if payload.replace(p).is_some() {
+                        return Err(Error::DuplicateInformationElement(PAYLOAD_IEI));
+                    }
+                }
+                InformationElement::Priority(p) => {
+                    if priority.replace(p).is_some() {
+                        return Err(Error::DuplicateInformationElement(PRIORITY_IEI));
+                    }
+                }
+            }
+        }
+        let header = header.ok_or(Error::NoHeader)?;
+        let payload = match payload {
+            Some(payload) if payload.is_empty() => return Err(Error::EmptyPayload),
+            Some(payload) if payload.len() > MAX_PAYLOAD_LENGTH => {
+                return Err(Error::PayloadTooLong(payload.len()))
+            }
+            Some(payload) => payload,
+            None if header.disposition_flags.allows_no_payload() => Vec::new(),
+            None => return Err(Error::NoPayload),
+        };
+        Ok(Message {
+            header,
+            payload,
+            priority,
+        })
+    }
+
+    /// Returns this message's header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns this message's unique client message id.
+    pub fn unique_client_message_id(&self) -> u32 {
+        self.header.unique_client_message_id
+    }
+
+    /// Returns this message's destination IMEI.
+    pub fn imei(&self) -> Imei {
+        self.header.imei
+    }
+
+    /// Returns this message's disposition flags.
+    pub fn disposition_flags(&self) -> DispositionFlags {
+        self.header.disposition_flags
+    }
+
+    /// Returns this message's payload.
+    ///
+    /// The payload is empty if the message doesn't have one.
+    pub fn payload(&self) -> &Vec<u8> {
+        &self.payload
+    }
+
+    /// Returns this message's priority, if it has a priority information element.
+    ///
+    /// The gateway treats a message without one as `Priority::LOWEST`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::{DispositionFlags, Header, Message, Priority};
+    /// let header = Header {
+    ///     unique_client_message_id: 1,
+    ///     imei: "300234063904190".parse().unwrap(),
+    ///     disposition_flags: DispositionFlags::empty(),
+    /// };
+    /// let message = Message::new(vec![
+    ///     header.into(),
+    ///     b"reboot".to_vec().into(),
+    ///     Priority::HIGHEST.into(),
+    /// ])
+    /// .unwrap();
+    /// assert_eq!(Some(Priority::HIGHEST), message.priority());
+    /// ```
+    pub fn priority(&self) -> Option<Priority> {
+        self.priority
+    }
+
+    /// Writes this message to an object that can `Write`.
+    #[cfg(feature = "std")]
+    pub fn write_to<W: Write>(&self, mut write: W) -> Result<(), Error> {
+        let mut bytes = vec![0; self.encoded_len()];
+        let _ = self.encode_into(&mut bytes)?;
+        write.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Returns the number of bytes needed to encode this message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::{DispositionFlags, Header, Message};
+    /// let header = Header {
+    ///     unique_client_message_id: 1,
+    ///     imei: "300234063904190".parse().unwrap(),
+    ///     disposition_flags: DispositionFlags::empty(),
+    /// };
+    /// let message = Message::new(vec![header.into(), b"hello".to_vec().into()]).unwrap();
+    /// assert_eq!(35, message.encoded_len());
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        3 + self.overall_message_length()
+    }
+
+    /// Encodes this message into the start of a byte slice.
+    ///
+    /// Returns the number of bytes written, or an error if the buffer is smaller than
+    /// `encoded_len()`.
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let needed = self.encoded_len();
+        if buffer.len() < needed {
+            return Err(Error::BufferTooSmall {
+                needed,
+                available: buffer.len(),
+            });
+        }
+        buffer[0] = PROTOCOL_REVISION_NUMBER;
+        BigEndian::write_u16(&mut buffer[1..3], self.overall_message_length() as u16);
+        let mut offset = 3;
+        offset += InformationElement::from(self.header).encode_into(&mut buffer[offset..])?;
+        if !self.payload.is_empty() {
+            offset += encode_payload(&self.payload, &mut buffer[offset..]);
+        }
+        if let Some(priority) = self.priority {
+            offset += InformationElement::from(priority).encode_into(&mut buffer[offset..])?;
+        }
+        Ok(offset)
+    }
+
+    fn overall_message_length(&self) -> usize {
+        let mut length = 24;
+        if !self.payload.is_empty() {
+            length += 3 + self.payload.len();
+        }
+        if let Some(priority) = self.priority {
+            length += InformationElement::from(priority).len();
+        }
+        length
+    }
+}
+
+/// Encodes the payload information element without copying the payload.
+///
+/// The payload length is checked when the message is created.
+fn encode_payload(payload: &[u8], buffer: &mut [u8]) -> usize {
+    buffer[0] = PAYLOAD_IEI;
+    BigEndian::write_u16(&mut buffer[1..3], payload.len() as u16);
+    buffer[3..3 + payload.len()].copy_from_slice(payload);
+    3 + payload.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header {
+            unique_client_message_id: 0x4d736731,
+            imei: "300034010123450".parse().unwrap(),
+            disposition_flags: DispositionFlags::empty(),
+        }
+    }
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = vec![1, 0, 32, 0x41, 0, 21];
+        bytes.extend_from_slice(b"Msg1300034010123450");
+        bytes.extend_from_slice(&[0, 0, 0x42, 0, 5]);
+        bytes.extend_from_slice(b"hello");
+        bytes
+    }
+
+    #[test]
+    fn read_from() {
+        let message = Message::read_from(&bytes()[..]).unwrap();
+        assert_eq!(0x4d736731, message.unique_client_message_id());
+        assert_eq!("300034010123450", message.imei());
+        assert!(message.disposition_flags().is_empty());
+        assert_eq!(b"hello", message.payload().as_slice());
+    }
+
+    #[test]
+    fn write_to() {
+        let message = Message::new(vec![header().into(), b"hello".to_vec().into()]).unwrap();
+        let mut written = Vec::new();
+        message.write_to(&mut written).unwrap();
+        assert_eq!(bytes(), written);
+        assert_eq!(written.len(), message.encoded_len());
+    }
+
+    #[test]
+    fn payload_order() {
+        let mut reordered = vec![1, 0, 32, 0x42, 0, 5];
+        reordered.extend_from_slice(b"hello");
+        reordered.extend_from_slice(&[0x41, 0, 21]);
+        reordered.extend_from_slice(b"Msg1300034010123450");
+        reordered.extend_from_slice(&[0, 0]);
+        assert_eq!(
+            Message::read_from(&bytes()[..]).unwrap(),
+            Message::read_from(&reordered[..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn payload_length() {
+        assert!(Message::new(vec![header().into(), vec![0; MAX_PAYLOAD_LENGTH].into()]).is_ok());
+        assert!(matches!(
+            Message::new(vec![
+                header().into(),
+                vec![0; MAX_PAYLOAD_LENGTH + 1].into()
+            ]),
+            Err(Error::PayloadTooLong(1891))
+        ));
+        assert!(matches!(
+            Message::new(vec![header().into(), Vec::new().into()]),
+            Err(Error::EmptyPayload)
+        ));
+    }
+
+    #[test]
+    fn missing_and_duplicate() {
+        assert!(matches!(
+            Message::new(vec![header().into()]),
+            Err(Error::NoPayload)
+        ));
+        assert!(matches!(
+            Message::new(vec![b"hi".to_vec().into()]),
+            Err(Error::NoHeader)
+        ));
+        assert!(matches!(
+            Message::new(vec![header().into(), header().into()]),
+            Err(Error::DuplicateInformationElement(0x41))
+        ));
+    }
+
+    #[test]
+    fn no_payload() {
+        let mut header = header();
+        assert!(matches!(
+            Message::new(vec![header.into()]),
+            Err(Error::NoPayload)
+        ));
+        header.disposition_flags = DispositionFlags::FLUSH_MT_QUEUE;
+        let message = Message::new(vec![header.into()]).unwrap();
+        assert!(message.payload().is_empty());
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        assert_eq!(27, bytes.len());
+        assert_eq!(message, Message::read_from(&bytes[..]).unwrap());
+        assert!(matches!(
+            Message::new(vec![header.into(), Vec::new().into()]),
+            Err(Error::EmptyPayload)
+        ));
+    }
+
+    #[test]
+    fn priority() {
+        let message = Message::new(vec![
+            Priority::new(2).unwrap().into(),
+            header().into(),
+            b"hello".to_vec().into(),
+        ])
+        .unwrap();
+        let mut written = Vec::new();
+        message.write_to(&mut written).unwrap();
+        let mut expected = bytes();
+        expected[2] += 5;
+        expected.extend_from_slice(&[0x46, 0, 2, 0, 2]);
+        assert_eq!(expected, written);
+        assert_eq!(message, Message::decode(&written).unwrap());
+        assert!(matches!(
+            Message::new(vec![
+                header().into(),
+                b"hello".to_vec().into(),
+                Priority::HIGHEST.into(),
+                Priority::LOWEST.into(),
+            ]),
+            Err(Error::DuplicateInformationElement(0x46))
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = bytes();
+        for i in 0..bytes.len() {
+            assert!(Message::read_from(&bytes[..i]).is_err());
+            assert!(Message::decode(&bytes[..i]).is_err());
+        }
+    }
+
+    #[test]
+    fn information_element_offset() {
+        let mut bytes = bytes();
+        bytes[27] = 0x43;
+        match Message::decode(&bytes).unwrap_err() {
+            Error::InformationElement { iei, offset, .. } => {
+                assert_eq!(0x43, iei);
+                assert_eq!(27, offset);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn invalid_protocol_revision_number() {
+        let mut bytes = bytes();
+        bytes[0] = 2;
+        assert!(matches!(
+            Message::decode(&bytes),
+            Err(Error::InvalidProtocolRevisionNumber(2))
+        ));
+    }
+
+    #[test]
+    fn encode_into_small_buffer() {
+        let message = Message::decode(&bytes()).unwrap();
+        let mut buffer = [0; 34];
+        assert!(matches!(
+            message.encode_into(&mut buffer),
+            Err(Error::BufferTooSmall {
+                needed: 35,
+                available: 34
+            })
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+//! Module for reading and writing Mobile-Terminated (MT) SBD messages.
+//!
+//! MT messages are sent from a vendor application to the Iridium gateway over `DirectIP`, which
+//! queues them for delivery to a device. They share the protocol revision number and the
+//! information element framing of mobile-originated messages, but have their own information
+//! elements.
+
+#[cfg(feature = "std")]
+mod client;
+mod confirmation;
+mod disposition;
+#[cfg(test)]
+pub(crate) mod fixtures;
+mod header;
+mod information_element;
+mod message;
+#[cfg(feature = "std")]
+mod outbox;
+
+#[cfg(feature = "std")]
+pub use self::client::{Client, RetryPolicy};
+#[cfg(feature = "std")]
+pub use self::outbox::{Outbox, OutboxEntry, OutboxEvent, OutboxEventKind, OutboxState};
+pub use self::{
+    confirmation::{Confirmation, ConfirmationStatus},
+    disposition::{DispositionFlags, Priority},
+    header::Header,
+    information_element::InformationElement,
+    message::{Message, MAX_PAYLOAD_LENGTH},
+};
@@ -0,0 +1,969 @@
+//! Keep track of mobile-terminated messages on their way to a device.
+//!
+//! An MT message goes through a few steps. It's queued, then sent to the gateway, which confirms
+//! that it queued the message. Later the device picks it up during a session, and the header of
+//! that session's MO message reports the MT message's MTMSN. The `Outbox` persists each message
+//! and its history on the filesystem, so those steps can happen in different processes and
+//! survive restarts.
+
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use log::{debug, info, warn};
+
+use crate::{
+    mo,
+    mt::{Client, ConfirmationStatus, DispositionFlags, Message},
+    Error, Imei,
+};
+
+const MT_DIRECTORY: &str = "mt";
+const MT_EXTENSION: &str = "mt";
+const TMP_EXTENSION: &str = "tmp";
+const LOG_EXTENSION: &str = "log";
+
+/// A persistent queue of MT messages, stored on the filesystem.
+///
+/// The outbox lives alongside a `FilesystemStorage` and can share its root directory. Each
+/// message is stored under its IMEI and named by its unique client message id, in a directory
+/// for its current state:
+///
+/// ```text
+/// <root>/<imei>/mt/pending/<id>.mt
+/// <root>/<imei>/mt/sent/<id>.mt
+/// <root>/<imei>/mt/delivered/<id>.mt
+/// <root>/<imei>/mt/failed/<id>.mt
+/// <root>/<imei>/mt/<id>.log
+/// ```
+///
+/// The `.log` file records every change of state, one event per line.
+///
+/// Messages are matched to deliveries by MTMSN. If the message's disposition flags include
+/// `ASSIGN_MTMSN`, its unique client message id is its MTMSN. Otherwise the gateway picks the
+/// MTMSN, and a delivery is matched to the oldest sent message that doesn't have one.
+///
+/// An outbox isn't locked, so only one process should change it at a time.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sbd::mt::{Client, DispositionFlags, Header, Message, Outbox};
+/// let outbox = Outbox::open("/var/iridium").unwrap();
+/// let header = Header {
+///     unique_client_message_id: 1,
+///     imei: "300234063904190".parse().unwrap(),
+///     disposition_flags: DispositionFlags::ASSIGN_MTMSN,
+/// };
+/// let message = Message::new(vec![header.into(), b"hello".to_vec().into()]).unwrap();
+/// outbox.enqueue(&message).unwrap();
+///
+/// let client = Client::new("12.47.179.12:10800").unwrap();
+/// outbox.send_pending(&client).unwrap();
+///
+/// let mo = sbd::mo::Message::from_path("/var/iridium/incoming.sbd").unwrap();
+/// if let Some(entry) = outbox.record_delivery(&mo).unwrap() {
+///     println!("delivered message {}", entry.unique_client_message_id());
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Outbox {
+    root: PathBuf,
+    max_attempts: u32,
+}
+
+/// Where an MT message is in the outbox.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OutboxState {
+    /// Waiting to be sent to the gateway.
+    Pending,
+    /// Queued by the gateway, but not yet picked up by the device.
+    Sent,
+    /// Picked up by the device.
+    Delivered,
+    /// Rejected by the gateway, or out of attempts.
+    Failed,
+}
+
+/// An MT message in the outbox, with its history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutboxEntry {
+    /// The message.
+    pub message: Message,
+    /// The current state of the message.
+    pub state: OutboxState,
+    /// Everything that has happened to the message, oldest first.
+    pub events: Vec<OutboxEvent>,
+}
+
+/// Something that happened to an MT message in the outbox.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutboxEvent {
+    /// When it happened.
+    pub time: DateTime<Utc>,
+    /// What happened.
+    pub kind: OutboxEventKind,
+}
+
+/// The kinds of `OutboxEvent`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutboxEventKind {
+    /// The message was added to the outbox.
+    Queued,
+    /// Sending failed, but the message will be sent again.
+    Retry {
+        /// Why sending failed.
+        reason: String,
+    },
+    /// The gateway accepted the message.
+    Sent {
+        /// The gateway's id for the message.
+        auto_id: u32,
+        /// The status from the gateway's confirmation.
+        status: ConfirmationStatus,
+    },
+    /// The device picked up the message.
+    ///
+    /// The time of this event is the time of session of the MO message that reported it.
+    Delivered {
+        /// The MTMSN reported by the device.
+        mtmsn: u16,
+        /// The MOMSN of the MO message that reported the delivery.
+        momsn: u16,
+    },
+    /// The message won't be sent again.
+    Failed {
+        /// Why the message failed.
+        reason: String,
+    },
+}
+
+impl Outbox {
+    /// Opens an outbox in a given directory.
+    ///
+    /// # Errors
+    ///
+    /// If the directory does not exist, returns an `Io` error of kind `NotFound`. If the path
+    /// isn't a directory, returns a `NotADirectory` error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::Outbox;
+    /// let outbox = Outbox::open("data").unwrap();
+    /// assert!(Outbox::open("not/a/directory").is_err());
+    /// ```
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Outbox, Error> {
+        let metadata = fs::metadata(root.as_ref())?;
+        if !metadata.is_dir() {
+            Err(Error::NotADirectory(root.as_ref().to_path_buf()))
+        } else {
+            Ok(Outbox {
+                root: root.as_ref().to_path_buf(),
+                max_attempts: 5,
+            })
+        }
+    }
+
+    /// Sets how many times a message is sent before it fails, which defaults to five.
+    ///
+    /// Connection problems, a full MT queue, and unavailable gateway resources use up an attempt.
+    /// Any other rejection fails the message right away.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Outbox {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Adds a message to the outbox as pending.
+    ///
+    /// # Errors
+    ///
+    /// Unique client message ids are unique per IMEI, so if the outbox already has a message with
+    /// this id for this IMEI, returns an `AlreadyExists` IO error.
+    pub fn enqueue(&self, message: &Message) -> Result<OutboxEntry, Error> {
+        let imei = message.imei();
+        let id = message.unique_client_message_id();
+        for state in OutboxState::ALL {
+            fs::create_dir_all(self.state_directory(imei, state))?;
+        }
+        // Write the message somewhere else first, so a crash can't leave a truncated message in
+        // pending. Hard links fail if the name is taken, which claims the id atomically.
+        let tmp_path = self.tmp_path(imei, id);
+        let mut file = File::create(&tmp_path)?;
+        let written = message
+            .write_to(&mut file)
+            .and_then(|()| file.sync_all().map_err(Error::from));
+        drop(file);
+        let path = self.message_path(imei, id, OutboxState::Pending);
+        let claimed = written.and_then(|()| fs::hard_link(&tmp_path, &path).map_err(Error::from));
+        fs::remove_file(&tmp_path)?;
+        claimed?;
+        if self.is_moved(imei, id) {
+            fs::remove_file(&path)?;
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists).into());
+        }
+        // Truncates any log left over from an enqueue that stopped before writing its message.
+        File::create(self.log_path(imei, id))?;
+        let event = self.append(imei, id, OutboxEventKind::Queued, Utc::now())?;
+        info!("Queued message {} for IMEI {}", id, imei);
+        Ok(OutboxEntry {
+            message: message.clone(),
+            state: OutboxState::Pending,
+            events: vec![event],
+        })
+    }
+
+    /// Sends every pending message to the gateway, oldest first.
+    ///
+    /// Messages the gateway accepts are moved to sent. Connection problems and transient
+    /// rejections leave a message pending until it runs out of attempts, and any other rejection
+    /// fails it. Since the gateway might queue a message even if its confirmation never arrives,
+    /// a message can be delivered more than once.
+    ///
+    /// Returns the messages that were sent, in their new states.
+    pub fn send_pending(&self, client: &Client) -> Result<Vec<OutboxEntry>, Error> {
+        let mut entries = Vec::new();
+        for imei in self.imeis()? {
+            for entry in self.pending(imei)? {
+                entries.push(self.send(client, entry)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Marks the message that an MO message reports as delivered.
+    ///
+    /// The MO message's MTMSN is matched against the sent messages for its IMEI, as described on
+    /// `Outbox`. A message without an assigned MTMSN is only matched if it was sent before the MO
+    /// session. Returns the delivered message, or `None` if the MO message doesn't report a
+    /// delivery or no sent message matches.
+    ///
+    /// The gateway sends an MO message again if it doesn't get a confirmation, so recording the
+    /// same MO message twice returns the message it already delivered instead of matching another.
+    pub fn record_delivery(&self, message: &mo::Message) -> Result<Option<OutboxEntry>, Error> {
+        let mtmsn = message.mtmsn();
+        if mtmsn == 0 || !message.session_status().is_success() {
+            return Ok(None);
+        }
+        let momsn = message.momsn();
+        let delivered = self.delivered(message.imei())?.into_iter().find(|entry| {
+            entry
+                .events
+                .iter()
+                .any(|event| event.kind == OutboxEventKind::Delivered { mtmsn, momsn })
+        });
+        if let Some(entry) = delivered {
+            debug!(
+                "Delivery of message {} for IMEI {} with MTMSN {} was already recorded",
+                entry.unique_client_message_id(),
+                message.imei(),
+                mtmsn
+            );
+            return Ok(Some(entry));
+        }
+        let sent = self.sent(message.imei())?;
+        let entry = match sent.iter().position(|e| e.mtmsn() == Some(mtmsn)) {
+            Some(i) => Some(i),
+            None => sent.iter().position(|e| {
+                e.mtmsn().is_none()
+                    && e.sent_at()
+                        .is_some_and(|sent_at| sent_at <= message.time_of_session())
+            }),
+        }
+        .map(|i| sent[i].clone());
+        let Some(mut entry) = entry else {
+            warn!(
+                "No sent message for IMEI {} matches MTMSN {}",
+                message.imei(),
+                mtmsn
+            );
+            return Ok(None);
+        };
+        let event = self.transition(
+            &entry,
+            OutboxState::Delivered,
+            OutboxEventKind::Delivered { mtmsn, momsn },
+            message.time_of_session(),
+        )?;
+        info!(
+            "Message {} for IMEI {} was delivered with MTMSN {}",
+            entry.unique_client_message_id(),
+            message.imei(),
+            mtmsn
+        );
+        entry.state = OutboxState::Delivered;
+        entry.events.push(event);
+        Ok(Some(entry))
+    }
+
+    /// Returns the IMEIs that have messages in the outbox.
+    pub fn imeis(&self) -> Result<Vec<Imei>, Error> {
+        let mut imeis = Vec::new();
+        for dir_entry in fs::read_dir(&self.root)? {
+            let path = dir_entry?.path();
+            if let Some(imei) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.parse::<Imei>().ok())
+            {
+                if path.join(MT_DIRECTORY).is_dir() {
+                    imeis.push(imei);
+                }
+            }
+        }
+        imeis.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Ok(imeis)
+    }
+
+    /// Returns the messages for an IMEI in a given state, oldest first.
+    pub fn entries(&self, imei: Imei, state: OutboxState) -> Result<Vec<OutboxEntry>, Error> {
+        let directory = self.state_directory(imei, state);
+        if !directory.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(directory)? {
+            let path = dir_entry?.path();
+            if path.extension().is_some_and(|e| e == MT_EXTENSION) {
+                let message = Message::read_from(File::open(&path)?)?;
+                let events = self.read_log(imei, message.unique_client_message_id())?;
+                entries.push(OutboxEntry {
+                    message,
+                    state,
+                    events,
+                });
+            }
+        }
+        entries.sort_by_key(|entry| (entry.queued_at(), entry.unique_client_message_id()));
+        Ok(entries)
+    }
+
+    /// Returns the messages for an IMEI that are waiting to be sent.
+    pub fn pending(&self, imei: Imei) -> Result<Vec<OutboxEntry>, Error> {
+        self.entries(imei, OutboxState::Pending)
+    }
+
+    /// Returns the messages for an IMEI that the gateway has queued.
+    pub fn sent(&self, imei: Imei) -> Result<Vec<OutboxEntry>, Error> {
+        self.entries(imei, OutboxState::Sent)
+    }
+
+    /// Returns the messages for an IMEI that the device has picked up.
+    pub fn delivered(&self, imei: Imei) -> Result<Vec<OutboxEntry>, Error> {
+        self.entries(imei, OutboxState::Delivered)
+    }
+
+    /// Returns the messages for an IMEI that have failed.
+    pub fn failed(&self, imei: Imei) -> Result<Vec<OutboxEntry>, Error> {
+        self.entries(imei, OutboxState::Failed)
+    }
+
+    /// Returns the message for an IMEI with a given unique client message id, if there is one.
+    pub fn entry(
+        &self,
+        imei: Imei,
+        unique_client_message_id: u32,
+    ) -> Result<Option<OutboxEntry>, Error> {
+        for state in OutboxState::ALL {
+            let path = self.message_path(imei, unique_client_message_id, state);
+            if path.is_file() {
+                return Ok(Some(OutboxEntry {
+                    message: Message::read_from(File::open(&path)?)?,
+                    state,
+                    events: self.read_log(imei, unique_client_message_id)?,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn send(&self, client: &Client, mut entry: OutboxEntry) -> Result<OutboxEntry, Error> {
+        let (state, kind) = match client.send(&entry.message) {
+            Ok(confirmation) if confirmation.status.is_success() => (
+                OutboxState::Sent,
+                OutboxEventKind::Sent {
+                    auto_id: confirmation.auto_id,
+                    status: confirmation.status,
+                },
+            ),
+            Ok(confirmation) if confirmation.status.is_transient() => {
+                self.retry_or_fail(&entry, confirmation.status.to_string())
+            }
+            Ok(confirmation) => (
+                OutboxState::Failed,
+                OutboxEventKind::Failed {
+                    reason: confirmation.status.to_string(),
+                },
+            ),
+            Err(err) => self.retry_or_fail(&entry, err.to_string()),
+        };
+        let event = self.transition(&entry, state, kind, Utc::now())?;
+        entry.state = state;
+        entry.events.push(event);
+        Ok(entry)
+    }
+
+    fn retry_or_fail(&self, entry: &OutboxEntry, reason: String) -> (OutboxState, OutboxEventKind) {
+        if entry.attempts() + 1 >= self.max_attempts {
+            warn!(
+                "Giving up on message {} for IMEI {}: {}",
+                entry.unique_client_message_id(),
+                entry.imei(),
+                reason
+            );
+            (OutboxState::Failed, OutboxEventKind::Failed { reason })
+        } else {
+            warn!(
+                "Could not send message {} for IMEI {}, will retry: {}",
+                entry.unique_client_message_id(),
+                entry.imei(),
+                reason
+            );
+            (OutboxState::Pending, OutboxEventKind::Retry { reason })
+        }
+    }
+
+    fn transition(
+        &self,
+        entry: &OutboxEntry,
+        state: OutboxState,
+        kind: OutboxEventKind,
+        time: DateTime<Utc>,
+    ) -> Result<OutboxEvent, Error> {
+        let imei = entry.imei();
+        let id = entry.unique_client_message_id();
+        if state != entry.state {
+            fs::rename(
+                self.message_path(imei, id, entry.state),
+                self.message_path(imei, id, state),
+            )?;
+        }
+        self.append(imei, id, kind, time)
+    }
+
+    fn append(
+        &self,
+        imei: Imei,
+        id: u32,
+        kind: OutboxEventKind,
+        time: DateTime<Utc>,
+    ) -> Result<OutboxEvent, Error> {
+        let event = OutboxEvent {
+            time: time.trunc_subsecs(6),
+            kind,
+        };
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(self.log_path(imei, id))?;
+        writeln!(file, "{}", event)?;
+        Ok(event)
+    }
+
+    fn is_moved(&self, imei: Imei, id: u32) -> bool {
+        OutboxState::ALL
+            .into_iter()
+            .filter(|&state| state != OutboxState::Pending)
+            .any(|state| self.message_path(imei, id, state).is_file())
+    }
+
+    fn read_log(&self, imei: Imei, id: u32) -> Result<Vec<OutboxEvent>, Error> {
+        let path = self.log_path(imei, id);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            // The message was written, but enqueue stopped before the log was.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut events = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let event = OutboxEvent::parse(&line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid outbox event in {}: {:?}", path.display(), line),
+                )
+            })?;
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    fn mt_directory(&self, imei: Imei) -> PathBuf {
+        let mut path = self.root.clone();
+        path.push(imei.as_str());
+        path.push(MT_DIRECTORY);
+        path
+    }
+
+    fn state_directory(&self, imei: Imei, state: OutboxState) -> PathBuf {
+        let mut path = self.mt_directory(imei);
+        path.push(state.directory());
+        path
+    }
+
+    fn message_path(&self, imei: Imei, id: u32, state: OutboxState) -> PathBuf {
+        let mut path = self.state_directory(imei, state);
+        path.push(format!("{:010}.{}", id, MT_EXTENSION));
+        path
+    }
+
+    fn tmp_path(&self, imei: Imei, id: u32) -> PathBuf {
+        let mut path = self.mt_directory(imei);
+        path.push(format!("{:010}.{}.{}", id, process::id(), TMP_EXTENSION));
+        path
+    }
+
+    fn log_path(&self, imei: Imei, id: u32) -> PathBuf {
+        let mut path = self.mt_directory(imei);
+        path.push(format!("{:010}.{}", id, LOG_EXTENSION));
+        path
+    }
+}
+
+impl OutboxState {
+    const ALL: [OutboxState; 4] = [
+        OutboxState::Pending,
+        OutboxState::Sent,
+        OutboxState::Delivered,
+        OutboxState::Failed,
+    ];
+
+    fn directory(&self) -> &'static str {
+        match *self {
+            OutboxState::Pending => "pending",
+            OutboxState::Sent => "sent",
+            OutboxState::Delivered => "delivered",
+            OutboxState::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for OutboxState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.directory())
+    }
+}
+
+impl OutboxEntry {
+    /// Returns the IMEI the message is for.
+    pub fn imei(&self) -> Imei {
+        self.message.imei()
+    }
+
+    /// Returns the message's unique client message id.
+    pub fn unique_client_message_id(&self) -> u32 {
+        self.message.unique_client_message_id()
+    }
+
+    /// Returns the message's MTMSN, if it's known.
+    ///
+    /// The MTMSN is known before delivery if the message has the `ASSIGN_MTMSN` disposition flag
+    /// and a unique client message id between 1 and 65,535. Otherwise it's known once the message
+    /// is delivered.
+    pub fn mtmsn(&self) -> Option<u16> {
+        if self
+            .message
+            .disposition_flags()
+            .contains(DispositionFlags::ASSIGN_MTMSN)
+        {
+            u16::try_from(self.unique_client_message_id())
+                .ok()
+                .filter(|&mtmsn| mtmsn != 0)
+        } else {
+            self.events.iter().find_map(|event| match event.kind {
+                OutboxEventKind::Delivered { mtmsn, .. } => Some(mtmsn),
+                _ => None,
+            })
+        }
+    }
+
+    /// Returns the number of times the message has been sent to the gateway.
+    pub fn attempts(&self) -> u32 {
+        self.events
+            .iter()
+            .filter(|event| !matches!(event.kind, OutboxEventKind::Queued))
+            .filter(|event| !matches!(event.kind, OutboxEventKind::Delivered { .. }))
+            .count() as u32
+    }
+
+    /// Returns when the message was added to the outbox.
+    pub fn queued_at(&self) -> Option<DateTime<Utc>> {
+        self.time_of(|kind| matches!(kind, OutboxEventKind::Queued))
+    }
+
+    /// Returns when the gateway accepted the message.
+    pub fn sent_at(&self) -> Option<DateTime<Utc>> {
+        self.time_of(|kind| matches!(kind, OutboxEventKind::Sent { .. }))
+    }
+
+    /// Returns the time of session in which the device picked up the message.
+    pub fn delivered_at(&self) -> Option<DateTime<Utc>> {
+        self.time_of(|kind| matches!(kind, OutboxEventKind::Delivered { .. }))
+    }
+
+    fn time_of<F: Fn(&OutboxEventKind) -> bool>(&self, f: F) -> Option<DateTime<Utc>> {
+        self.events
+            .iter()
+            .find(|event| f(&event.kind))
+            .map(|event| event.time)
+    }
+}
+
+impl OutboxEvent {
+    fn parse(line: &str) -> Option<OutboxEvent> {
+        let mut fields = line.split('\t');
+        let time = DateTime::parse_from_rfc3339(fields.next()?)
+            .ok()?
+            .with_timezone(&Utc);
+        let kind = match fields.next()? {
+            "queued" => OutboxEventKind::Queued,
+            "retry" => OutboxEventKind::Retry {
+                reason: fields.next()?.to_string(),
+            },
+            "sent" => OutboxEventKind::Sent {
+                auto_id: fields.next()?.parse().ok()?,
+                status: ConfirmationStatus::from(fields.next()?.parse::<i16>().ok()?),
+            },
+            "delivered" => OutboxEventKind::Delivered {
+                mtmsn: fields.next()?.parse().ok()?,
+                momsn: fields.next()?.parse().ok()?,
+            },
+            "failed" => OutboxEventKind::Failed {
+                reason: fields.next()?.to_string(),
+            },
+            _ => return None,
+        };
+        if fields.next().is_some() {
+            None
+        } else {
+            Some(OutboxEvent { time, kind })
+        }
+    }
+}
+
+impl fmt::Display for OutboxEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t",
+            self.time.to_rfc3339_opts(SecondsFormat::Micros, true)
+        )?;
+        match self.kind {
+            OutboxEventKind::Queued => write!(f, "queued"),
+            OutboxEventKind::Retry { ref reason } => write!(f, "retry\t{}", one_field(reason)),
+            OutboxEventKind::Sent { auto_id, status } => {
+                write!(f, "sent\t{}\t{}", auto_id, status.code())
+            }
+            OutboxEventKind::Delivered { mtmsn, momsn } => {
+                write!(f, "delivered\t{}\t{}", mtmsn, momsn)
+            }
+            OutboxEventKind::Failed { ref reason } => write!(f, "failed\t{}", one_field(reason)),
+        }
+    }
+}
+
+/// Replaces the characters that separate fields and events.
+fn one_field(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        mt::fixtures::{client, imei, mt_message, IMEI},
+        testing::{MockGateway, Response},
+    };
+
+    fn mo_message(mtmsn: u16) -> mo::Message {
+        mo::Message::builder()
+            .imei(IMEI)
+            .momsn(7)
+            .mtmsn(mtmsn)
+            .time_of_session((Utc::now() + Duration::minutes(1)).trunc_subsecs(0))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn open() {
+        Outbox::open(TempDir::new("").unwrap().path()).unwrap();
+        assert!(Outbox::open("not/a/real/directory").is_err());
+        assert!(Outbox::open("data/0-mo.sbd").is_err());
+    }
+
+    #[test]
+    fn enqueue() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let message = mt_message(1, DispositionFlags::empty());
+        let entry = outbox.enqueue(&message).unwrap();
+        assert_eq!(OutboxState::Pending, entry.state);
+        assert_eq!(vec![imei()], outbox.imeis().unwrap());
+        assert_eq!(vec![entry.clone()], outbox.pending(imei()).unwrap());
+        assert_eq!(Some(entry), outbox.entry(imei(), 1).unwrap());
+        assert!(outbox.entry(imei(), 2).unwrap().is_none());
+        assert!(tempdir
+            .path()
+            .join("300234063904190/mt/pending/0000000001.mt")
+            .is_file());
+        assert!(outbox.enqueue(&message).is_err());
+    }
+
+    #[test]
+    fn enqueue_after_interrupted_enqueue() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let mt = tempdir.path().join("300234063904190/mt");
+        fs::create_dir_all(&mt).unwrap();
+        fs::write(mt.join("0000000001.1.tmp"), [1, 0]).unwrap();
+        assert!(outbox.pending(imei()).unwrap().is_empty());
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        assert_eq!(1, outbox.pending(imei()).unwrap().len());
+        let tmp_files = fs::read_dir(&mt)
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .unwrap_or_default()
+                    == TMP_EXTENSION
+            })
+            .count();
+        assert_eq!(1, tmp_files);
+    }
+
+    #[test]
+    fn enqueue_duplicate_of_sent_message() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let message = mt_message(1, DispositionFlags::empty());
+        outbox.enqueue(&message).unwrap();
+        let mt = tempdir.path().join("300234063904190/mt");
+        fs::rename(
+            mt.join("pending/0000000001.mt"),
+            mt.join("sent/0000000001.mt"),
+        )
+        .unwrap();
+        assert!(outbox.enqueue(&message).is_err());
+        assert!(!mt.join("pending/0000000001.mt").exists());
+        assert_eq!(
+            OutboxState::Sent,
+            outbox.entry(imei(), 1).unwrap().unwrap().state
+        );
+    }
+
+    #[test]
+    fn enqueue_over_orphan_log() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let mt = tempdir.path().join("300234063904190/mt");
+        fs::create_dir_all(&mt).unwrap();
+        fs::write(mt.join("0000000001.log"), "not an event\n").unwrap();
+        let entry = outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        assert_eq!(1, entry.events.len());
+        assert_eq!(Some(entry), outbox.entry(imei(), 1).unwrap());
+    }
+
+    #[test]
+    fn message_without_log() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        fs::remove_file(tempdir.path().join("300234063904190/mt/0000000001.log")).unwrap();
+        let pending = outbox.pending(imei()).unwrap();
+        assert_eq!(1, pending.len());
+        assert!(pending[0].events.is_empty());
+    }
+
+    #[test]
+    fn shares_root_with_filesystem_storage() {
+        use crate::storage::{FilesystemStorage, Storage};
+
+        let tempdir = TempDir::new("").unwrap();
+        let mut storage = FilesystemStorage::open(tempdir.path()).unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        storage
+            .store(mo::Message::from_path("data/0-mo.sbd").unwrap())
+            .unwrap();
+        assert_eq!(1, storage.messages().unwrap().len());
+        assert_eq!(1, outbox.pending(imei()).unwrap().len());
+    }
+
+    #[test]
+    fn send_and_deliver() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let gateway = MockGateway::start().unwrap();
+        outbox
+            .enqueue(&mt_message(42, DispositionFlags::ASSIGN_MTMSN))
+            .unwrap();
+
+        let sent = outbox.send_pending(&client(&gateway)).unwrap();
+        assert_eq!(1, sent.len());
+        assert_eq!(OutboxState::Sent, sent[0].state);
+        assert_eq!(Some(42), sent[0].mtmsn());
+        assert!(outbox.pending(imei()).unwrap().is_empty());
+        assert_eq!(sent, outbox.sent(imei()).unwrap());
+        assert_eq!(1, gateway.messages().len());
+
+        assert!(outbox.record_delivery(&mo_message(0)).unwrap().is_none());
+        assert!(outbox.record_delivery(&mo_message(41)).unwrap().is_none());
+        let mo = mo_message(42);
+        let delivered = outbox.record_delivery(&mo).unwrap().unwrap();
+        assert_eq!(OutboxState::Delivered, delivered.state);
+        assert_eq!(Some(mo.time_of_session()), delivered.delivered_at());
+        assert!(outbox.sent(imei()).unwrap().is_empty());
+        assert_eq!(vec![delivered], outbox.delivered(imei()).unwrap());
+    }
+
+    #[test]
+    fn deliver_without_assigned_mtmsn() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let gateway = MockGateway::start().unwrap();
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        outbox
+            .enqueue(&mt_message(2, DispositionFlags::empty()))
+            .unwrap();
+        outbox.send_pending(&client(&gateway)).unwrap();
+        assert_eq!(None, outbox.sent(imei()).unwrap()[0].mtmsn());
+
+        let delivered = outbox.record_delivery(&mo_message(300)).unwrap().unwrap();
+        assert_eq!(1, delivered.unique_client_message_id());
+        assert_eq!(Some(300), delivered.mtmsn());
+        let delivered = outbox.record_delivery(&mo_message(301)).unwrap().unwrap();
+        assert_eq!(2, delivered.unique_client_message_id());
+        assert!(outbox.record_delivery(&mo_message(302)).unwrap().is_none());
+    }
+
+    #[test]
+    fn record_delivery_twice() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let gateway = MockGateway::start().unwrap();
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        outbox
+            .enqueue(&mt_message(2, DispositionFlags::empty()))
+            .unwrap();
+        outbox.send_pending(&client(&gateway)).unwrap();
+
+        let mo = mo_message(300);
+        let delivered = outbox.record_delivery(&mo).unwrap().unwrap();
+        assert_eq!(1, delivered.unique_client_message_id());
+        assert_eq!(Some(delivered), outbox.record_delivery(&mo).unwrap());
+        let sent = outbox.sent(imei()).unwrap();
+        assert_eq!(1, sent.len());
+        assert_eq!(2, sent[0].unique_client_message_id());
+    }
+
+    #[test]
+    fn retry_transient_rejection() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let gateway = MockGateway::start().unwrap();
+        gateway.push_response(Response::Status(ConfirmationStatus::QueueFull));
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+
+        let entries = outbox.send_pending(&client(&gateway)).unwrap();
+        assert_eq!(OutboxState::Pending, entries[0].state);
+        assert!(matches!(
+            entries[0].events[1].kind,
+            OutboxEventKind::Retry { .. }
+        ));
+        let entries = outbox.send_pending(&client(&gateway)).unwrap();
+        assert_eq!(OutboxState::Sent, entries[0].state);
+        assert_eq!(2, entries[0].attempts());
+        assert_eq!(entries, outbox.sent(imei()).unwrap());
+    }
+
+    #[test]
+    fn permanent_rejection() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let gateway = MockGateway::start().unwrap();
+        gateway.push_response(Response::Status(ConfirmationStatus::UnknownImei));
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        let entries = outbox.send_pending(&client(&gateway)).unwrap();
+        assert_eq!(OutboxState::Failed, entries[0].state);
+        assert_eq!(entries, outbox.failed(imei()).unwrap());
+        assert!(outbox.send_pending(&client(&gateway)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn out_of_attempts() {
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap().max_attempts(2);
+        let gateway = MockGateway::start().unwrap();
+        gateway.set_default_response(Response::DropConnection);
+        outbox
+            .enqueue(&mt_message(1, DispositionFlags::empty()))
+            .unwrap();
+        let entries = outbox.send_pending(&client(&gateway)).unwrap();
+        assert_eq!(OutboxState::Pending, entries[0].state);
+        let entries = outbox.send_pending(&client(&gateway)).unwrap();
+        assert_eq!(OutboxState::Failed, entries[0].state);
+        assert_eq!(2, entries[0].attempts());
+    }
+
+    #[test]
+    fn event_roundtrip() {
+        let time = Utc.with_ymd_and_hms(2017, 10, 1, 12, 30, 0).unwrap();
+        for kind in [
+            OutboxEventKind::Queued,
+            OutboxEventKind::Retry {
+                reason: "io error:\tconnection\nrefused".to_string(),
+            },
+            OutboxEventKind::Sent {
+                auto_id: 1234,
+                status: ConfirmationStatus::Queued(3),
+            },
+            OutboxEventKind::Delivered {
+                mtmsn: 42,
+                momsn: 7,
+            },
+            OutboxEventKind::Failed {
+                reason: ConfirmationStatus::UnknownImei.to_string(),
+            },
+        ] {
+            let event = OutboxEvent { time, kind };
+            let parsed = OutboxEvent::parse(&event.to_string()).unwrap();
+            assert_eq!(event.time, parsed.time);
+            match (event.kind, parsed.kind) {
+                (OutboxEventKind::Retry { .. }, OutboxEventKind::Retry { reason }) => {
+                    assert_eq!("io error: connection refused", reason)
+                }
+                (expected, actual) => assert_eq!(expected, actual),
+            }
+        }
+        assert!(OutboxEvent::parse("not an event").is_none());
+        assert!(OutboxEvent::parse(&format!("{}\tsent\t1", time.to_rfc3339())).is_none());
+    }
+}
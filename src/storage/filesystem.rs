@@ -7,7 +7,7 @@ use std::{
 
 use walkdir;
 
-use crate::{mo::Message, storage, Error};
+use crate::{mo::Message, storage, Error, Imei};
 
 const SBD_EXTENSION: &str = "sbd";
 
@@ -79,7 +79,7 @@ impl Storage {
 impl storage::Storage for Storage {
     fn store(&mut self, message: Message) -> Result<(), Error> {
         let mut path_buf = self.root.clone();
-        path_buf.push(message.imei());
+        path_buf.push(message.imei().as_str());
         path_buf.push(message.time_of_session().format("%Y").to_string());
         path_buf.push(message.time_of_session().format("%m").to_string());
         fs::create_dir_all(&path_buf)?;
@@ -98,9 +98,9 @@ impl storage::Storage for Storage {
         self.iter().collect()
     }
 
-    fn messages_from_imei(&self, imei: &str) -> Result<Vec<Message>, Error> {
+    fn messages_from_imei(&self, imei: Imei) -> Result<Vec<Message>, Error> {
         let mut path = self.root.clone();
-        path.push(imei);
+        path.push(imei.as_str());
         StorageIterator::new(&path).collect()
     }
 }
@@ -184,9 +184,13 @@ mod tests {
         let mut storage = Storage::open(tempdir.path()).unwrap();
         let message = Message::from_path("data/0-mo.sbd").unwrap();
         storage.store(message.clone()).unwrap();
-        let messages = storage.messages_from_imei("300234063904190").unwrap();
+        let messages = storage
+            .messages_from_imei("300234063904190".parse().unwrap())
+            .unwrap();
         assert_eq!(vec![message], messages);
-        let messages = storage.messages_from_imei("300234063904191").unwrap();
+        let messages = storage
+            .messages_from_imei("300234063904191".parse().unwrap())
+            .unwrap();
         assert!(messages.is_empty());
     }
 }
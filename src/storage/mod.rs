@@ -4,7 +4,7 @@ mod filesystem;
 mod memory;
 
 pub use self::{filesystem::Storage as FilesystemStorage, memory::Storage as MemoryStorage};
-use crate::{mo::Message, Error};
+use crate::{mo::Message, Error, Imei};
 
 /// Basic storage operations.
 pub trait Storage {
@@ -49,12 +49,12 @@ pub trait Storage {
     /// let message = Message::from_path("data/0-mo.sbd").unwrap();
     /// let mut storage = MemoryStorage::new();
     /// storage.store(message.clone());
-    /// let messages = storage.messages_from_imei("300234063904190").unwrap();
+    /// let messages = storage.messages_from_imei("300234063904190".parse().unwrap()).unwrap();
     /// assert_eq!(vec![message], messages);
-    /// let messages = storage.messages_from_imei("300234063904191").unwrap();
+    /// let messages = storage.messages_from_imei("300234063904191".parse().unwrap()).unwrap();
     /// assert!(messages.is_empty());
     /// ```
-    fn messages_from_imei(&self, imei: &str) -> Result<Vec<Message>, Error> {
+    fn messages_from_imei(&self, imei: Imei) -> Result<Vec<Message>, Error> {
         self.messages().map(|mut v| {
             v.retain(|m| m.imei() == imei);
             v
@@ -0,0 +1,409 @@
+//! Test helpers that stand in for the Iridium gateway.
+//!
+//! `MockGateway` listens on a local port and speaks the MT side of `DirectIP`, so code that sends
+//! MT messages can be tested without an Iridium account.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use log::{debug, warn};
+
+use crate::{
+    mt::{Confirmation, ConfirmationStatus, DispositionFlags, Message},
+    Imei,
+};
+
+/// The most messages the gateway will queue for one IMEI.
+const MAX_QUEUE_LENGTH: u8 = 50;
+
+/// A local stand-in for the Iridium gateway's MT `DirectIP` service.
+///
+/// The gateway accepts connections on a local port, reads one MT message per connection, records
+/// it, and replies according to its `Response`s. By default every message is queued, and the
+/// confirmation holds the message's position in its IMEI's queue. The gateway shuts down when it
+/// is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use sbd::mt::{Client, ConfirmationStatus, DispositionFlags, Header, Message};
+/// use sbd::testing::{MockGateway, Response};
+/// let gateway = MockGateway::start().unwrap();
+/// gateway.push_response(Response::Status(ConfirmationStatus::QueueFull));
+///
+/// let client = Client::new(gateway.addr()).unwrap();
+/// let header = Header {
+///     unique_client_message_id: 1,
+///     imei: "300234063904190".parse().unwrap(),
+///     disposition_flags: DispositionFlags::empty(),
+/// };
+/// let message = Message::new(vec![header.into(), b"hello".to_vec().into()]).unwrap();
+/// assert_eq!(ConfirmationStatus::QueueFull, client.send(&message).unwrap().status);
+/// assert_eq!(ConfirmationStatus::Queued(1), client.send(&message).unwrap().status);
+/// assert_eq!(2, gateway.messages().len());
+/// ```
+#[derive(Debug)]
+pub struct MockGateway {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// How a `MockGateway` responds to an MT message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    /// Queue the message, and reply with its position in its IMEI's queue.
+    ///
+    /// The gateway behaves like the real one: a full queue replies with
+    /// `ConfirmationStatus::QueueFull`, and flushing the MT queue empties it.
+    Queue,
+    /// Reply with this status, without queueing the message.
+    Status(ConfirmationStatus),
+    /// Wait, then respond with the inner response.
+    Delay(Duration, Box<Response>),
+    /// Close the connection without replying.
+    DropConnection,
+    /// Reply with these bytes instead of a confirmation.
+    Garbage(Vec<u8>),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    messages: Vec<Message>,
+    responses: VecDeque<Response>,
+    default_response: Option<Response>,
+    queues: HashMap<Imei, u8>,
+    next_auto_id: u32,
+}
+
+impl MockGateway {
+    /// Starts a gateway on a free local port.
+    pub fn start() -> io::Result<MockGateway> {
+        MockGateway::bind("127.0.0.1:0")
+    }
+
+    /// Starts a gateway on the given address.
+    pub fn bind(addr: &str) -> io::Result<MockGateway> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            next_auto_id: 1,
+            ..Default::default()
+        }));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let state = Arc::clone(&state);
+            let shutdown = Arc::clone(&shutdown);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    match stream {
+                        Ok(stream) => {
+                            let state = Arc::clone(&state);
+                            thread::spawn(move || handle_stream(stream, &state));
+                        }
+                        Err(err) => warn!("Mock gateway could not accept a connection: {}", err),
+                    }
+                }
+            })
+        };
+        Ok(MockGateway {
+            addr,
+            state,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// Returns the address the gateway is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Adds a response to the end of the script.
+    ///
+    /// Each message uses up the first response in the script. Once the script is empty, the
+    /// gateway falls back to its default response.
+    pub fn push_response(&self, response: Response) {
+        self.state().responses.push_back(response);
+    }
+
+    /// Sets the response used once the script is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbd::mt::ConfirmationStatus;
+    /// use sbd::testing::{MockGateway, Response};
+    /// let gateway = MockGateway::start().unwrap();
+    /// gateway.set_default_response(Response::Status(ConfirmationStatus::InvalidImei));
+    /// ```
+    pub fn set_default_response(&self, response: Response) {
+        self.state().default_response = Some(response);
+    }
+
+    /// Returns every message the gateway has received, in the order they arrived.
+    ///
+    /// Messages are recorded whatever the response, including messages that were rejected or
+    /// whose connection was dropped.
+    pub fn messages(&self) -> Vec<Message> {
+        self.state().messages.clone()
+    }
+
+    /// Returns the number of messages queued for an IMEI.
+    pub fn queue_length(&self, imei: Imei) -> u8 {
+        self.state().queues.get(&imei).copied().unwrap_or(0)
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("unable to lock mock gateway state")
+    }
+}
+
+impl Drop for MockGateway {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it sees the shutdown flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl State {
+    fn next_response(&mut self) -> Response {
+        self.responses
+            .pop_front()
+            .or_else(|| self.default_response.clone())
+            .unwrap_or(Response::Queue)
+    }
+
+    fn queue(&mut self, message: &Message) -> ConfirmationStatus {
+        let length = self.queues.entry(message.imei()).or_insert(0);
+        if message
+            .disposition_flags()
+            .contains(DispositionFlags::FLUSH_MT_QUEUE)
+        {
+            *length = 0;
+        }
+        if message.payload().is_empty() {
+            ConfirmationStatus::SuccessNoPayload
+        } else if *length >= MAX_QUEUE_LENGTH {
+            ConfirmationStatus::QueueFull
+        } else {
+            *length += 1;
+            ConfirmationStatus::Queued(*length)
+        }
+    }
+}
+
+fn handle_stream(mut stream: TcpStream, state: &Mutex<State>) {
+    let message = match Message::read_from(&mut stream) {
+        Ok(message) => message,
+        Err(err) => {
+            warn!("Mock gateway could not read an MT message: {}", err);
+            return;
+        }
+    };
+    debug!(
+        "Mock gateway received message {} for IMEI {}",
+        message.unique_client_message_id(),
+        message.imei()
+    );
+    let response = {
+        let mut state = state.lock().expect("unable to lock mock gateway state");
+        state.messages.push(message.clone());
+        state.next_response()
+    };
+    respond(stream, state, &message, response);
+}
+
+fn respond(mut stream: TcpStream, state: &Mutex<State>, message: &Message, response: Response) {
+    let status = match response {
+        Response::Queue => state
+            .lock()
+            .expect("unable to lock mock gateway state")
+            .queue(message),
+        Response::Status(status) => status,
+        Response::Delay(delay, response) => {
+            thread::sleep(delay);
+            return respond(stream, state, message, *response);
+        }
+        Response::DropConnection => return,
+        Response::Garbage(bytes) => {
+            if let Err(err) = stream.write_all(&bytes) {
+                warn!("Mock gateway could not write garbage: {}", err);
+            }
+            return;
+        }
+    };
+    let auto_id = if status.queue_position().is_some() {
+        let mut state = state.lock().expect("unable to lock mock gateway state");
+        state.next_auto_id += 1;
+        state.next_auto_id - 1
+    } else {
+        0
+    };
+    let confirmation = Confirmation {
+        unique_client_message_id: message.unique_client_message_id(),
+        imei: message.imei(),
+        auto_id,
+        status,
+    };
+    if let Err(err) = confirmation.write_to(&mut stream) {
+        warn!("Mock gateway could not write a confirmation: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        mt::fixtures::{client, header, imei, mt_message},
+        Error,
+    };
+
+    #[test]
+    fn queue_positions() {
+        let gateway = MockGateway::start().unwrap();
+        let client = client(&gateway);
+        let message = mt_message(7, DispositionFlags::empty());
+        for position in 1..=MAX_QUEUE_LENGTH {
+            let confirmation = client.send(&message).unwrap();
+            assert_eq!(ConfirmationStatus::Queued(position), confirmation.status);
+            assert_eq!(u32::from(position), confirmation.auto_id);
+        }
+        let confirmation = client.send(&message).unwrap();
+        assert_eq!(ConfirmationStatus::QueueFull, confirmation.status);
+        assert_eq!(0, confirmation.auto_id);
+        assert_eq!(50, gateway.queue_length(message.imei()));
+
+        let flush = Message::new(vec![header(7, DispositionFlags::FLUSH_MT_QUEUE).into()]).unwrap();
+        assert_eq!(
+            ConfirmationStatus::SuccessNoPayload,
+            client.send(&flush).unwrap().status
+        );
+        assert_eq!(0, gateway.queue_length(message.imei()));
+        assert_eq!(52, gateway.messages().len());
+    }
+
+    #[test]
+    fn scripted_statuses() {
+        let gateway = MockGateway::start().unwrap();
+        gateway.push_response(Response::Status(ConfirmationStatus::InvalidImei));
+        gateway.set_default_response(Response::Status(ConfirmationStatus::UnknownImei));
+        let client = client(&gateway);
+        let message = mt_message(7, DispositionFlags::empty());
+        assert_eq!(
+            ConfirmationStatus::InvalidImei,
+            client.send(&message).unwrap().status
+        );
+        assert_eq!(
+            ConfirmationStatus::UnknownImei,
+            client.send(&message).unwrap().status
+        );
+        assert_eq!(0, gateway.queue_length(message.imei()));
+    }
+
+    #[test]
+    fn delay() {
+        let gateway = MockGateway::start().unwrap();
+        gateway.push_response(Response::Delay(
+            Duration::from_millis(100),
+            Box::new(Response::Queue),
+        ));
+        let start = Instant::now();
+        assert!(client(&gateway)
+            .send(&mt_message(7, DispositionFlags::empty()))
+            .unwrap()
+            .status
+            .is_success());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        gateway.push_response(Response::Delay(
+            Duration::from_millis(500),
+            Box::new(Response::Queue),
+        ));
+        let client = client(&gateway).read_timeout(Some(Duration::from_millis(50)));
+        assert!(matches!(
+            client.send(&mt_message(7, DispositionFlags::empty())),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn drop_connection() {
+        let gateway = MockGateway::start().unwrap();
+        gateway.push_response(Response::DropConnection);
+        assert!(matches!(
+            client(&gateway).send(&mt_message(7, DispositionFlags::empty())),
+            Err(Error::Io(_))
+        ));
+        assert_eq!(1, gateway.messages().len());
+    }
+
+    #[test]
+    fn garbage() {
+        let gateway = MockGateway::start().unwrap();
+        gateway.push_response(Response::Garbage(vec![1, 0, 3, 0x44, 0, 0]));
+        assert!(client(&gateway)
+            .send(&mt_message(7, DispositionFlags::empty()))
+            .is_err());
+    }
+
+    #[test]
+    fn confirmation_for_another_message() {
+        let gateway = MockGateway::start().unwrap();
+        let mut bytes = Vec::new();
+        Confirmation {
+            unique_client_message_id: 8,
+            imei: imei(),
+            auto_id: 1,
+            status: ConfirmationStatus::Queued(1),
+        }
+        .write_to(&mut bytes)
+        .unwrap();
+        gateway.push_response(Response::Garbage(bytes));
+        match client(&gateway).send(&mt_message(7, DispositionFlags::empty())) {
+            Err(Error::ConfirmationMismatch {
+                sent_id: 7,
+                confirmed_id: 8,
+                ..
+            }) => {}
+            other => panic!("expected a confirmation mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_message() {
+        let gateway = MockGateway::start().unwrap();
+        let mut stream = TcpStream::connect(gateway.addr()).unwrap();
+        stream.write_all(b"not an mt message").unwrap();
+        drop(stream);
+        assert_eq!(
+            ConfirmationStatus::Queued(1),
+            client(&gateway)
+                .send(&mt_message(7, DispositionFlags::empty()))
+                .unwrap()
+                .status
+        );
+        assert_eq!(1, gateway.messages().len());
+    }
+}
@@ -0,0 +1,472 @@
+//! Line up the messages sent to and from a device.
+//!
+//! The header of each MO message has an MTMSN, which is the MT message the device received during
+//! that session, or zero if it didn't receive one. Given the MO messages in a `Storage` and the MT
+//! messages that were sent, with their MTMSNs if they're known, this module builds a timeline for
+//! each device that shows which MT message was delivered in which session, how long it took, and
+//! which MT messages never arrived.
+//!
+//! # Examples
+//!
+//! ```
+//! use chrono::{TimeZone, Utc};
+//! use sbd::storage::{MemoryStorage, Storage};
+//! use sbd::timeline::{self, SentMt};
+//!
+//! let imei = "300234063904190".parse().unwrap();
+//! let sent = vec![SentMt {
+//!     imei,
+//!     unique_client_message_id: 42,
+//!     mtmsn: Some(42),
+//!     sent_at: Utc.with_ymd_and_hms(2017, 10, 1, 0, 0, 0).unwrap(),
+//! }];
+//! let mut storage = MemoryStorage::new();
+//! storage
+//!     .store(
+//!         sbd::mo::Message::builder()
+//!             .imei("300234063904190")
+//!             .mtmsn(42)
+//!             .time_of_session(Utc.with_ymd_and_hms(2017, 10, 1, 0, 5, 0).unwrap())
+//!             .build()
+//!             .unwrap(),
+//!     )
+//!     .unwrap();
+//!
+//! let timeline = timeline::timeline(&storage, imei, &sent).unwrap();
+//! let delivery = &timeline.deliveries()[0];
+//! assert_eq!(42, delivery.mt.unique_client_message_id);
+//! assert_eq!(5, delivery.latency.num_minutes());
+//! assert!(timeline.undelivered().is_empty());
+//! ```
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{mo, mt::OutboxEntry, storage::Storage, Error, Imei};
+
+/// An MT message that was sent to the gateway.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SentMt {
+    /// The IMEI the message was sent to.
+    pub imei: Imei,
+    /// The message's unique client message id.
+    pub unique_client_message_id: u32,
+    /// The MTMSN the message was assigned, if it's known.
+    ///
+    /// The gateway assigns MTMSNs itself unless the message asks for its unique client message id
+    /// to be used, so the MTMSN of most messages isn't known until they're delivered.
+    pub mtmsn: Option<u16>,
+    /// When the gateway accepted the message.
+    pub sent_at: DateTime<Utc>,
+}
+
+/// The messages sent to and from one device, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timeline {
+    /// The device's IMEI.
+    pub imei: Imei,
+    /// Everything that happened, oldest first.
+    pub entries: Vec<TimelineEntry>,
+}
+
+/// One thing that happened in a `Timeline`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimelineEntry {
+    /// An MT message was sent to the gateway.
+    Sent(SentMt),
+    /// The device had a session and sent an MO message.
+    Session {
+        /// The MO message.
+        message: mo::Message,
+        /// The MT message that was delivered during the session, if any.
+        delivery: Option<Delivery>,
+    },
+}
+
+/// An MT message that was delivered during a session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Delivery {
+    /// The MT message.
+    pub mt: SentMt,
+    /// The time from sending the MT message to the session that delivered it.
+    pub latency: Duration,
+}
+
+/// Builds the timeline for one device.
+///
+/// MT messages for other devices are ignored.
+///
+/// Each MO message's MTMSN is matched to the most recent MT message with that MTMSN that was sent
+/// at or before the session and hasn't already been delivered. If no MT message has that MTMSN at
+/// all, it's matched to the oldest undelivered MT message without a known MTMSN that was sent at or
+/// before the session. Sessions that failed, or that have an MTMSN of zero, don't deliver anything.
+///
+/// An MO message that was received more than once, with the same MOMSN and time of session, only
+/// appears in the timeline once.
+pub fn timeline<S: Storage + ?Sized>(
+    storage: &S,
+    imei: Imei,
+    sent: &[SentMt],
+) -> Result<Timeline, Error> {
+    let messages = storage.messages_from_imei(imei)?;
+    Ok(build(imei, messages, sent))
+}
+
+/// Builds a timeline for every device in the storage or in the sent MT messages.
+///
+/// Timelines are ordered by IMEI.
+pub fn timelines<S: Storage + ?Sized>(
+    storage: &S,
+    sent: &[SentMt],
+) -> Result<Vec<Timeline>, Error> {
+    let messages = storage.messages()?;
+    let imeis: BTreeSet<Imei> = messages
+        .iter()
+        .map(|message| message.imei())
+        .chain(sent.iter().map(|mt| mt.imei))
+        .collect();
+    Ok(imeis
+        .into_iter()
+        .map(|imei| {
+            let messages = messages
+                .iter()
+                .filter(|message| message.imei() == imei)
+                .cloned()
+                .collect();
+            build(imei, messages, sent)
+        })
+        .collect())
+}
+
+fn build(imei: Imei, mut messages: Vec<mo::Message>, sent: &[SentMt]) -> Timeline {
+    let mut sent: Vec<SentMt> = sent.iter().filter(|mt| mt.imei == imei).copied().collect();
+    sent.sort_by_key(|mt| mt.sent_at);
+    messages.sort_by_key(|message| (message.time_of_session(), message.momsn()));
+
+    let mut delivered = vec![false; sent.len()];
+    let mut seen = BTreeSet::new();
+    let mut sessions = Vec::with_capacity(messages.len());
+    for message in messages {
+        if !seen.insert((message.momsn(), message.time_of_session())) {
+            continue;
+        }
+        let mtmsn = message.mtmsn();
+        let time_of_session = message.time_of_session();
+        let delivery = if mtmsn != 0 && message.session_status().is_success() {
+            let candidates = || {
+                sent.iter()
+                    .enumerate()
+                    .filter(|&(i, mt)| !delivered[i] && mt.sent_at <= time_of_session)
+            };
+            if sent.iter().any(|mt| mt.mtmsn == Some(mtmsn)) {
+                candidates().rev().find(|(_, mt)| mt.mtmsn == Some(mtmsn))
+            } else {
+                candidates().find(|(_, mt)| mt.mtmsn.is_none())
+            }
+            .map(|(i, mt)| {
+                delivered[i] = true;
+                Delivery {
+                    mt: *mt,
+                    latency: time_of_session - mt.sent_at,
+                }
+            })
+        } else {
+            None
+        };
+        sessions.push(TimelineEntry::Session { message, delivery });
+    }
+
+    let mut entries: Vec<TimelineEntry> = sent.into_iter().map(TimelineEntry::Sent).collect();
+    entries.extend(sessions);
+    // Stable, so an MT message sent in the same instant as a session comes first.
+    entries.sort_by_key(|entry| entry.time());
+    Timeline { imei, entries }
+}
+
+impl SentMt {
+    /// Returns the sent MT message for an outbox entry.
+    ///
+    /// Returns `None` if the message hasn't been sent.
+    pub fn from_outbox_entry(entry: &OutboxEntry) -> Option<SentMt> {
+        Some(SentMt {
+            imei: entry.imei(),
+            unique_client_message_id: entry.unique_client_message_id(),
+            mtmsn: entry.mtmsn(),
+            sent_at: entry.sent_at()?,
+        })
+    }
+}
+
+impl Timeline {
+    /// Returns the MT messages that were delivered, in order of delivery.
+    pub fn deliveries(&self) -> Vec<&Delivery> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match *entry {
+                TimelineEntry::Session {
+                    delivery: Some(ref delivery),
+                    ..
+                } => Some(delivery),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the MT messages that were sent but never delivered, oldest first.
+    pub fn undelivered(&self) -> Vec<&SentMt> {
+        let deliveries = self.deliveries();
+        self.entries
+            .iter()
+            .filter_map(|entry| match *entry {
+                TimelineEntry::Sent(ref mt) => Some(mt),
+                TimelineEntry::Session { .. } => None,
+            })
+            .filter(|&mt| !deliveries.iter().any(|delivery| delivery.mt == *mt))
+            .collect()
+    }
+}
+
+impl TimelineEntry {
+    /// Returns when this entry happened.
+    ///
+    /// That's when an MT message was sent, or the time of session of an MO message.
+    pub fn time(&self) -> DateTime<Utc> {
+        match *self {
+            TimelineEntry::Sent(ref mt) => mt.sent_at,
+            TimelineEntry::Session { ref message, .. } => message.time_of_session(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{
+        mo::SessionStatus,
+        mt::fixtures::{client, imei, mt_message, IMEI},
+        storage::MemoryStorage,
+    };
+
+    fn minutes(minutes: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2017, 10, 1, 0, 0, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    fn sent(unique_client_message_id: u32, mtmsn: u16, sent_at: i64) -> SentMt {
+        SentMt {
+            imei: imei(),
+            unique_client_message_id,
+            mtmsn: Some(mtmsn),
+            sent_at: minutes(sent_at),
+        }
+    }
+
+    fn mo_message(momsn: u16, mtmsn: u16, time_of_session: i64) -> mo::Message {
+        mo::Message::builder()
+            .imei(IMEI)
+            .momsn(momsn)
+            .mtmsn(mtmsn)
+            .time_of_session(minutes(time_of_session))
+            .build()
+            .unwrap()
+    }
+
+    fn storage(messages: Vec<mo::Message>) -> MemoryStorage {
+        let mut storage = MemoryStorage::new();
+        for message in messages {
+            storage.store(message).unwrap();
+        }
+        storage
+    }
+
+    #[test]
+    fn deliveries() {
+        let storage = storage(vec![
+            mo_message(2, 11, 30),
+            mo_message(1, 0, 5),
+            mo_message(3, 0, 40),
+        ]);
+        let sent = vec![sent(1, 10, 0), sent(2, 11, 10)];
+        let timeline = timeline(&storage, imei(), &sent).unwrap();
+        let times: Vec<_> = timeline.entries.iter().map(TimelineEntry::time).collect();
+        assert_eq!(
+            vec![
+                minutes(0),
+                minutes(5),
+                minutes(10),
+                minutes(30),
+                minutes(40)
+            ],
+            times
+        );
+        let deliveries = timeline.deliveries();
+        assert_eq!(1, deliveries.len());
+        assert_eq!(sent[1], deliveries[0].mt);
+        assert_eq!(Duration::minutes(20), deliveries[0].latency);
+        assert_eq!(vec![&sent[0]], timeline.undelivered());
+    }
+
+    #[test]
+    fn not_delivered_before_it_was_sent() {
+        let storage = storage(vec![mo_message(1, 10, 5)]);
+        let timeline = timeline(&storage, imei(), &[sent(1, 10, 10)]).unwrap();
+        assert!(timeline.deliveries().is_empty());
+        assert_eq!(1, timeline.undelivered().len());
+    }
+
+    #[test]
+    fn reused_mtmsn() {
+        let storage = storage(vec![mo_message(1, 10, 5), mo_message(2, 10, 25)]);
+        let sent = vec![sent(1, 10, 0), sent(2, 10, 20)];
+        let timeline = timeline(&storage, imei(), &sent).unwrap();
+        let deliveries = timeline.deliveries();
+        assert_eq!(sent[0], deliveries[0].mt);
+        assert_eq!(sent[1], deliveries[1].mt);
+        assert_eq!(Duration::minutes(5), deliveries[1].latency);
+    }
+
+    #[test]
+    fn unknown_mtmsn() {
+        let storage = storage(vec![
+            mo_message(1, 7, 5),
+            mo_message(2, 10, 25),
+            mo_message(3, 8, 30),
+        ]);
+        let unknown = |id, at| SentMt {
+            mtmsn: None,
+            ..sent(id, 0, at)
+        };
+        let sent = vec![unknown(1, 0), unknown(2, 1), sent(3, 10, 20)];
+        let timeline = timeline(&storage, imei(), &sent).unwrap();
+        let deliveries = timeline.deliveries();
+        assert_eq!(3, deliveries.len());
+        assert_eq!(sent[0], deliveries[0].mt);
+        assert_eq!(sent[2], deliveries[1].mt);
+        assert_eq!(sent[1], deliveries[2].mt);
+        assert!(timeline.undelivered().is_empty());
+    }
+
+    #[test]
+    fn duplicate_mo_message() {
+        let storage = storage(vec![
+            mo_message(1, 10, 5),
+            mo_message(1, 10, 5),
+            mo_message(2, 11, 10),
+        ]);
+        let unknown = SentMt {
+            mtmsn: None,
+            ..sent(2, 0, 0)
+        };
+        let sent = vec![sent(1, 10, 0), unknown];
+        let timeline = timeline(&storage, imei(), &sent).unwrap();
+        assert_eq!(4, timeline.entries.len());
+        let deliveries = timeline.deliveries();
+        assert_eq!(2, deliveries.len());
+        assert_eq!(sent[0], deliveries[0].mt);
+        assert_eq!(sent[1], deliveries[1].mt);
+    }
+
+    #[test]
+    fn known_mtmsn_already_delivered() {
+        let storage = storage(vec![mo_message(1, 10, 5), mo_message(2, 10, 10)]);
+        let unknown = SentMt {
+            mtmsn: None,
+            ..sent(2, 0, 0)
+        };
+        let sent = vec![sent(1, 10, 0), unknown];
+        let timeline = timeline(&storage, imei(), &sent).unwrap();
+        let deliveries = timeline.deliveries();
+        assert_eq!(1, deliveries.len());
+        assert_eq!(sent[0], deliveries[0].mt);
+        assert_eq!(vec![&sent[1]], timeline.undelivered());
+    }
+
+    #[test]
+    fn failed_session() {
+        let mut storage = MemoryStorage::new();
+        storage
+            .store(
+                mo::Message::builder()
+                    .imei(IMEI)
+                    .mtmsn(10)
+                    .session_status(SessionStatus::Timeout)
+                    .time_of_session(minutes(5))
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        let timeline = timeline(&storage, imei(), &[sent(1, 10, 0)]).unwrap();
+        assert!(timeline.deliveries().is_empty());
+    }
+
+    #[test]
+    fn timelines_per_device() {
+        let other: Imei = "300034010123450".parse().unwrap();
+        let storage = storage(vec![mo_message(1, 10, 5)]);
+        let sent = vec![
+            sent(1, 10, 0),
+            SentMt {
+                imei: other,
+                unique_client_message_id: 1,
+                mtmsn: Some(10),
+                sent_at: minutes(0),
+            },
+        ];
+        let timelines = timelines(&storage, &sent).unwrap();
+        assert_eq!(2, timelines.len());
+        let timeline = timelines.iter().find(|t| t.imei == imei()).unwrap();
+        assert_eq!(1, timeline.deliveries().len());
+        let timeline = timelines.iter().find(|t| t.imei == other).unwrap();
+        assert_eq!(vec![&sent[1]], timeline.undelivered());
+    }
+
+    #[test]
+    fn from_outbox_entry() {
+        use tempdir::TempDir;
+
+        use crate::{
+            mt::{DispositionFlags, Outbox},
+            testing::MockGateway,
+        };
+
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        let entry = outbox
+            .enqueue(&mt_message(42, DispositionFlags::ASSIGN_MTMSN))
+            .unwrap();
+        assert_eq!(None, SentMt::from_outbox_entry(&entry));
+
+        let gateway = MockGateway::start().unwrap();
+        let entry = &outbox.send_pending(&client(&gateway)).unwrap()[0];
+        let sent = SentMt::from_outbox_entry(entry).unwrap();
+        assert_eq!(Some(42), sent.mtmsn);
+        assert_eq!(entry.sent_at(), Some(sent.sent_at));
+    }
+
+    #[test]
+    fn undelivered_outbox_entry() {
+        use tempdir::TempDir;
+
+        use crate::{
+            mt::{DispositionFlags, Outbox},
+            testing::MockGateway,
+        };
+
+        let tempdir = TempDir::new("").unwrap();
+        let outbox = Outbox::open(tempdir.path()).unwrap();
+        outbox
+            .enqueue(&mt_message(42, DispositionFlags::empty()))
+            .unwrap();
+        let gateway = MockGateway::start().unwrap();
+        let entry = &outbox.send_pending(&client(&gateway)).unwrap()[0];
+        let sent = SentMt::from_outbox_entry(entry).unwrap();
+        assert_eq!(None, sent.mtmsn);
+
+        let storage = storage(vec![mo_message(1, 0, 5)]);
+        let timeline = timeline(&storage, imei(), &[sent]).unwrap();
+        assert!(timeline.deliveries().is_empty());
+        assert_eq!(vec![&sent], timeline.undelivered());
+    }
+}